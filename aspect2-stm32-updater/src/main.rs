@@ -5,7 +5,7 @@ use binrw::{
 };
 use clap::{Parser, Subcommand, ValueEnum};
 use indicatif::{ProgressBar, ProgressStyle};
-use libaspect2::{Ft4232h, I2cFtBitbang};
+use libaspect2::Facet2Board;
 use std::fmt::Display;
 use std::fs::File;
 use std::io::{Cursor, Read, Write};
@@ -108,6 +108,12 @@ macro_rules! pagecount_for_size {
 #[derive(Parser)]
 #[command(name = "aspect2-stm32-updater", version = "1.0")]
 struct Args {
+    /// Serial number of the adapter to use (without channel letter)
+    #[arg(long, global = true)]
+    serial: Option<String>,
+    /// Index of the adapter to use
+    #[arg(long, global = true, conflicts_with = "serial")]
+    index: Option<usize>,
     /// Command to execute
     #[command(subcommand)]
     command: Command,
@@ -152,8 +158,10 @@ fn main() -> Result<()> {
         std::thread::sleep(Duration::from_nanos(nanos));
     }
 
-    let dev = Ft4232h::with_description("Facet2 FabA+ C")?;
-    let mut i2c_if = I2cFtBitbang::new(dev);
+    // The bootloader stretches the clock while erasing and programming
+    let mut i2c_if = Facet2Board::select(args.serial.as_deref(), args.index)?
+        .i2c()?
+        .with_stretch_timeout(Duration::from_secs(2));
 
     let mut config = stm32_bootloader_client::Config::i2c_address(STM32_BOOTLOADER_I2C_ADDR);
     config.mass_erase_max_ns = Duration::from_secs(1).as_nanos() as u64;
@@ -0,0 +1,166 @@
+//! Block device support
+//!
+//! Re-exports `embedded-sdmmc`'s block interface, which the eMMC reader
+//! implements so filesystem crates can run on top of it.
+//! [`BlockReader`] turns any block device into a byte stream implementing the
+//! `embedded-io` (and with `std`, the `std::io`) `Read`/`Seek` traits.
+
+use embedded_io::SeekFrom;
+use crate::prelude::*;
+use crate::error::{Error, ErrorKind};
+
+pub use embedded_sdmmc::{Block, BlockCount, BlockDevice, BlockIdx};
+
+/// Size of a single block in bytes
+pub const BLOCK_SIZE: usize = Block::LEN;
+
+/// Byte-addressed, read-only view of a [`BlockDevice`]
+///
+/// Keeps the last block read cached, so small sequential reads only touch
+/// the device once per block.
+pub struct BlockReader<B> {
+    device: B,
+    position: u64,
+    cache: Block,
+    cached_block: Option<u32>,
+}
+
+impl<B: BlockDevice<Error = Error>> BlockReader<B> {
+    pub fn new(device: B) -> Self {
+        Self {
+            device,
+            position: 0,
+            cache: Block::new(),
+            cached_block: None,
+        }
+    }
+
+    pub fn into_inner(self) -> B {
+        self.device
+    }
+
+    fn len(&mut self) -> Result<u64, Error> {
+        Ok(u64::from(self.device.num_blocks()?.0) * BLOCK_SIZE as u64)
+    }
+
+    fn read_bytes(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let len = self.len()?;
+        if self.position >= len || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let block = (self.position / BLOCK_SIZE as u64) as u32;
+        if self.cached_block != Some(block) {
+            self.cached_block = None;
+            self.device
+                .read(core::slice::from_mut(&mut self.cache), BlockIdx(block))?;
+            self.cached_block = Some(block);
+        }
+
+        let offset = (self.position % BLOCK_SIZE as u64) as usize;
+        let count = buf.len().min(BLOCK_SIZE - offset);
+        buf[..count].copy_from_slice(&self.cache.contents[offset..offset + count]);
+        self.position += count as u64;
+        Ok(count)
+    }
+
+    fn seek_bytes(&mut self, pos: SeekFrom) -> Result<u64, Error> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len()?.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = new_pos.ok_or(ErrorKind::SeekOutOfRange)?;
+        Ok(self.position)
+    }
+}
+
+impl<B> embedded_io::ErrorType for BlockReader<B> {
+    type Error = Error;
+}
+
+impl<B: BlockDevice<Error = Error>> embedded_io::Read for BlockReader<B> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.read_bytes(buf)
+    }
+}
+
+impl<B: BlockDevice<Error = Error>> embedded_io::Seek for BlockReader<B> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Error> {
+        self.seek_bytes(pos)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<B: BlockDevice<Error = Error>> std::io::Read for BlockReader<B> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        Ok(self.read_bytes(buf)?)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<B: BlockDevice<Error = Error>> std::io::Seek for BlockReader<B> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            std::io::SeekFrom::Start(offset) => SeekFrom::Start(offset),
+            std::io::SeekFrom::End(offset) => SeekFrom::End(offset),
+            std::io::SeekFrom::Current(offset) => SeekFrom::Current(offset),
+        };
+        Ok(self.seek_bytes(pos)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    /// Block `n` is filled with `n as u8`
+    struct MockDisk {
+        blocks: u32,
+        reads: Cell<usize>,
+    }
+
+    impl BlockDevice for MockDisk {
+        type Error = Error;
+
+        fn read(&self, blocks: &mut [Block], start_block_idx: BlockIdx) -> Result<(), Self::Error> {
+            for (i, block) in blocks.iter_mut().enumerate() {
+                block.contents.fill((start_block_idx.0 as usize + i) as u8);
+            }
+            self.reads.set(self.reads.get() + 1);
+            Ok(())
+        }
+
+        fn write(&self, _blocks: &[Block], _start_block_idx: BlockIdx) -> Result<(), Self::Error> {
+            Err(ErrorKind::Unsupported { operation: "write" }.into())
+        }
+
+        fn num_blocks(&self) -> Result<BlockCount, Self::Error> {
+            Ok(BlockCount(self.blocks))
+        }
+    }
+
+    #[test]
+    fn test_block_reader() {
+        use embedded_io::{Read, Seek};
+
+        let mut reader = BlockReader::new(MockDisk { blocks: 4, reads: Cell::new(0) });
+
+        reader.seek(SeekFrom::Start(510)).unwrap();
+        let mut buf = [0u8; 4];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [0, 0, 1, 1]);
+
+        // Reads within the cached block don't hit the device
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [1, 1, 1, 1]);
+        assert_eq!(reader.device.reads.get(), 2);
+
+        assert_eq!(reader.seek(SeekFrom::End(-1)).unwrap(), 4 * 512 - 1);
+        assert_eq!(reader.read(&mut buf).unwrap(), 1);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+
+        assert!(reader.seek(SeekFrom::Current(-5000)).is_err());
+    }
+}
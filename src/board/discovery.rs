@@ -0,0 +1,277 @@
+//! Discovery of FACET / ASPECT2 debug adapters
+//!
+//! D2XX lists every FT4232H channel as a separate device. [`list_adapters`]
+//! groups those entries back into physical adapters, so tools can pick a
+//! board by serial number or index when several are attached to one host.
+use std::fmt;
+
+use libftd2xx::{DeviceInfo, DeviceType};
+
+use super::facet2::Channel;
+use crate::error::{Error, ErrorKind};
+
+/// Adapter family, derived from the EEPROM description
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdapterFamily {
+    /// FACET (first revision)
+    Facet,
+    /// FACET2
+    Facet2,
+    /// ASPECT2
+    Aspect2,
+}
+
+impl AdapterFamily {
+    /// Description prefixes, longest first so `Facet2` is not taken for `Facet`
+    const PREFIXES: [(&'static str, AdapterFamily); 3] = [
+        ("facet2", AdapterFamily::Facet2),
+        ("aspect2", AdapterFamily::Aspect2),
+        ("facet", AdapterFamily::Facet),
+    ];
+
+    /// Detect the family and return the remainder of the description
+    fn parse(description: &str) -> Option<(Self, &str)> {
+        Self::PREFIXES.into_iter().find_map(|(prefix, family)| {
+            let head = description.get(..prefix.len())?;
+            head.eq_ignore_ascii_case(prefix)
+                .then(|| (family, &description[prefix.len()..]))
+        })
+    }
+}
+
+impl fmt::Display for AdapterFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Facet => write!(f, "FACET"),
+            Self::Facet2 => write!(f, "FACET2"),
+            Self::Aspect2 => write!(f, "ASPECT2"),
+        }
+    }
+}
+
+/// A connected adapter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdapterInfo {
+    /// Adapter family
+    pub family: AdapterFamily,
+    /// Serial number without channel letter, e.g. `FT1234`
+    pub serial: String,
+    /// Description without channel letter, e.g. `Facet2 FabA+`
+    pub description: String,
+    /// Board revision from the description, e.g. `FabA+`
+    pub revision: Option<String>,
+    /// USB location ID of the adapter's first channel
+    ///
+    /// Identifies the port the adapter is plugged into, stable as long as
+    /// the USB topology does not change.
+    pub location: u32,
+    /// Channels that were enumerated
+    pub channels: Vec<Channel>,
+    /// Whether any channel is already opened by another process
+    pub in_use: bool,
+}
+
+impl fmt::Display for AdapterInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} serial={} description=\"{}\" revision={} location={:#06x} channels=",
+            self.family,
+            self.serial,
+            self.description,
+            self.revision.as_deref().unwrap_or("?"),
+            self.location
+        )?;
+        for channel in &self.channels {
+            write!(f, "{}", channel.suffix())?;
+        }
+        if self.in_use {
+            write!(f, " (in use)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Split a per-channel D2XX name into its base name and channel
+///
+/// Works for both serial numbers (`FT1234A`) and descriptions (`Facet2 FabA+ A`).
+pub(crate) fn split_channel(name: &str) -> Option<(&str, Channel)> {
+    let suffix = name.chars().last()?;
+    let channel = Channel::from_suffix(suffix)?;
+    let base = &name[..name.len() - suffix.len_utf8()];
+    Some((base.trim_end(), channel))
+}
+
+/// List all FACET / ASPECT2 adapters attached to the host
+pub fn list_adapters() -> Result<Vec<AdapterInfo>, Error> {
+    let devices = libftd2xx::list_devices()?;
+    let locations = location_ids(devices.len())?;
+    Ok(group_adapters(&devices, &locations))
+}
+
+/// USB location IDs of the entries in the device list
+///
+/// `libftd2xx::DeviceInfo` does not carry them, so they are read with
+/// `FT_GetDeviceInfoDetail` from the list `list_devices` just created.
+fn location_ids(count: usize) -> Result<Vec<u32>, Error> {
+    use libftd2xx_ffi::{DWORD, FT_GetDeviceInfoDetail, FT_HANDLE};
+
+    (0..count)
+        .map(|index| {
+            let (mut flags, mut device_type, mut id, mut location): (DWORD, DWORD, DWORD, DWORD) = (0, 0, 0, 0);
+            let mut serial = [0u8; 16];
+            let mut description = [0u8; 64];
+            let mut handle: FT_HANDLE = core::ptr::null_mut();
+            // SAFETY: all pointers are valid for the duration of the call and
+            // the buffers have the sizes the D2XX programmer's guide requires
+            let status = unsafe {
+                FT_GetDeviceInfoDetail(
+                    index as DWORD,
+                    &mut flags,
+                    &mut device_type,
+                    &mut id,
+                    &mut location,
+                    serial.as_mut_ptr().cast(),
+                    description.as_mut_ptr().cast(),
+                    &mut handle,
+                )
+            };
+            if status != 0 {
+                return Err(libftd2xx::FtStatus::from(status).into());
+            }
+            Ok(location)
+        })
+        .collect()
+}
+
+/// Find an adapter by serial number (without channel letter)
+pub fn find_by_serial(serial: &str) -> Result<AdapterInfo, Error> {
+    list_adapters()?
+        .into_iter()
+        .find(|adapter| adapter.serial == serial)
+        .ok_or(ErrorKind::BoardNotFound.into())
+}
+
+/// Find an adapter by its index in [`list_adapters`]
+pub fn find_by_index(index: usize) -> Result<AdapterInfo, Error> {
+    list_adapters()?
+        .into_iter()
+        .nth(index)
+        .ok_or(ErrorKind::BoardNotFound.into())
+}
+
+/// Group the per-channel device entries into adapters
+///
+/// `locations` holds the USB location ID of each entry in `devices`.
+fn group_adapters(devices: &[DeviceInfo], locations: &[u32]) -> Vec<AdapterInfo> {
+    let mut adapters: Vec<AdapterInfo> = vec![];
+    for (info, &location) in devices.iter().zip(locations) {
+        if info.device_type != DeviceType::FT4232H {
+            continue;
+        }
+
+        let (Some((serial, channel)), Some((description, _))) = (
+            split_channel(&info.serial_number),
+            split_channel(&info.description),
+        ) else {
+            continue;
+        };
+
+        let Some((family, rest)) = AdapterFamily::parse(description) else {
+            continue;
+        };
+
+        if let Some(adapter) = adapters.iter_mut().find(|a| a.serial == serial) {
+            adapter.channels.push(channel);
+            adapter.in_use |= info.port_open;
+            continue;
+        }
+
+        let revision = rest.trim();
+        adapters.push(AdapterInfo {
+            family,
+            serial: serial.to_string(),
+            description: description.to_string(),
+            revision: (!revision.is_empty()).then(|| revision.to_string()),
+            location,
+            channels: vec![channel],
+            in_use: info.port_open,
+        });
+    }
+    adapters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(serial: &str, description: &str) -> DeviceInfo {
+        DeviceInfo {
+            port_open: false,
+            speed: None,
+            device_type: DeviceType::FT4232H,
+            vendor_id: 0x0403,
+            product_id: 0x6011,
+            serial_number: serial.to_string(),
+            description: description.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_split_channel() {
+        assert_eq!(split_channel("FT1234A"), Some(("FT1234", Channel::A)));
+        assert_eq!(
+            split_channel("Facet2 FabA+ D"),
+            Some(("Facet2 FabA+", Channel::D))
+        );
+        assert_eq!(split_channel("FT1234"), None);
+        assert_eq!(split_channel(""), None);
+    }
+
+    #[test]
+    fn test_family() {
+        assert_eq!(
+            AdapterFamily::parse("Facet2 FabA+"),
+            Some((AdapterFamily::Facet2, " FabA+"))
+        );
+        assert_eq!(
+            AdapterFamily::parse("FACET FabB"),
+            Some((AdapterFamily::Facet, " FabB"))
+        );
+        assert_eq!(
+            AdapterFamily::parse("ASPECT2"),
+            Some((AdapterFamily::Aspect2, ""))
+        );
+        assert_eq!(AdapterFamily::parse("Quad RS232-HS"), None);
+    }
+
+    #[test]
+    fn test_group_adapters() {
+        let mut devices = vec![device("FT9999A", "Quad RS232-HS A")];
+        for serial in ["FT0001", "FT0002"] {
+            for channel in Channel::ALL {
+                devices.push(device(
+                    &format!("{serial}{}", channel.suffix()),
+                    &format!("Facet2 FabA+ {}", channel.suffix()),
+                ));
+            }
+        }
+        devices[6].port_open = true;
+        let locations: Vec<u32> = (0..devices.len() as u32).map(|i| 0x1010 + i).collect();
+
+        let adapters = group_adapters(&devices, &locations);
+        assert_eq!(adapters.len(), 2);
+
+        assert_eq!(adapters[0].serial, "FT0001");
+        assert_eq!(adapters[0].family, AdapterFamily::Facet2);
+        assert_eq!(adapters[0].description, "Facet2 FabA+");
+        assert_eq!(adapters[0].revision.as_deref(), Some("FabA+"));
+        assert_eq!(adapters[0].location, 0x1011);
+        assert_eq!(adapters[0].channels, Channel::ALL.to_vec());
+        assert!(!adapters[0].in_use);
+
+        assert_eq!(adapters[1].serial, "FT0002");
+        assert_eq!(adapters[1].location, 0x1015);
+        assert!(adapters[1].in_use);
+    }
+}
@@ -0,0 +1,372 @@
+//! FACET2 debug board
+//!
+//! The FACET2 carries a FT4232H whose four channels are wired to:
+//!
+//! | Channel | Function               |
+//! |---------|------------------------|
+//! | A       | eMMC SPI               |
+//! | B       | SB JTAG                |
+//! | C       | I2C / kernel debug     |
+//! | D       | SMC UART               |
+//!
+//! D2XX enumerates each channel as its own device, with the channel letter
+//! appended to both the serial number and the description
+//! (e.g. `FT1234A` / `Facet2 FabA+ A`).
+use std::io;
+use std::time::Duration;
+
+use libftd2xx::{BitMode, Ft4232h, FtdiCommon, FtdiMpsse};
+
+use super::discovery::{self, AdapterFamily, AdapterInfo};
+use crate::error::{Error, ErrorKind};
+use crate::i2c::i2c_bitbang::I2cFtBitbang;
+use crate::pinmap::{PinMap, Signal};
+use crate::spi::backend::ftdi::FtdiBackend;
+
+/// Default SMC UART baud rate
+const UART_BAUD_RATE: u32 = 115_200;
+
+/// FT4232H channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    /// eMMC SPI
+    A,
+    /// SB JTAG
+    B,
+    /// I2C / kernel debug
+    C,
+    /// SMC UART
+    D,
+}
+
+impl Channel {
+    /// All channels in enumeration order
+    pub const ALL: [Channel; 4] = [Channel::A, Channel::B, Channel::C, Channel::D];
+
+    /// Letter D2XX appends to the serial number and description
+    pub fn suffix(self) -> char {
+        match self {
+            Self::A => 'A',
+            Self::B => 'B',
+            Self::C => 'C',
+            Self::D => 'D',
+        }
+    }
+
+    /// Parse a channel from its letter
+    pub fn from_suffix(suffix: char) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.suffix() == suffix)
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// FACET2 board owning the four channels of its FT4232H
+///
+/// All channels are opened once when the board is created. A channel that
+/// is already opened by another process (e.g. the UART in a terminal) does
+/// not keep the others from being used, its open error is reported when the
+/// channel is requested.
+#[derive(Debug)]
+pub struct Facet2Board {
+    serial: String,
+    description: String,
+    pin_maps: [PinMap; 4],
+    channels: [Result<Ft4232h, Error>; 4],
+}
+
+impl Facet2Board {
+    /// Open all channels of a discovered adapter
+    ///
+    /// The FACET2 pin layout is used for all channels, see [`Facet2Board::with_pin_map`]
+    /// for adapters wired differently. Adapters of other families are
+    /// rejected with [`ErrorKind::UnsupportedAdapter`].
+    pub fn from_adapter(adapter: &AdapterInfo) -> Result<Self, Error> {
+        if adapter.family != AdapterFamily::Facet2 {
+            return Err(ErrorKind::UnsupportedAdapter { family: adapter.family }.into());
+        }
+        let channels = Channel::ALL.map(|channel| {
+            let serial = format!("{}{}", adapter.serial, channel.suffix());
+            Ok(Ft4232h::with_serial_number(&serial)?)
+        });
+        if channels.iter().all(Result::is_err) {
+            return Err(ErrorKind::BoardNotFound.into());
+        }
+        Ok(Self::with_channels(adapter, channels))
+    }
+
+    fn with_channels(adapter: &AdapterInfo, channels: [Result<Ft4232h, Error>; 4]) -> Self {
+        Self {
+            serial: adapter.serial.clone(),
+            description: adapter.description.clone(),
+            pin_maps: [
+                PinMap::facet2_spi(),
+                PinMap::facet2_jtag(),
+                PinMap::facet2_i2c(),
+                PinMap::empty(),
+            ],
+            channels,
+        }
+    }
+
+    /// List all FACET2 boards attached to the host
+    ///
+    /// Nothing is opened, use [`Facet2Board::from_adapter`] on the chosen entry.
+    pub fn list() -> Result<Vec<AdapterInfo>, Error> {
+        Ok(discovery::list_adapters()?
+            .into_iter()
+            .filter(|adapter| adapter.family == AdapterFamily::Facet2)
+            .collect())
+    }
+
+    /// Open the board with the given serial number
+    ///
+    /// The serial number is the one of the board (`FT1234`), without the channel letter.
+    pub fn open(serial: &str) -> Result<Self, Error> {
+        Self::from_adapter(&discovery::find_by_serial(serial)?)
+    }
+
+    /// Open the first FACET2 board found
+    pub fn first() -> Result<Self, Error> {
+        let adapter = Self::list()?.into_iter().next().ok_or(Error::from(ErrorKind::BoardNotFound))?;
+        Self::from_adapter(&adapter)
+    }
+
+    /// Open a board by serial number or by index in [`discovery::list_adapters`]
+    ///
+    /// The serial number takes precedence. Without either, the first FACET2 board is used.
+    pub fn select(serial: Option<&str>, index: Option<usize>) -> Result<Self, Error> {
+        match (serial, index) {
+            (Some(serial), _) => Self::open(serial),
+            (None, Some(index)) => Self::from_adapter(&discovery::find_by_index(index)?),
+            (None, None) => Self::first(),
+        }
+    }
+
+    /// Board serial number (without channel letter)
+    pub fn serial(&self) -> &str {
+        &self.serial
+    }
+
+    /// Board description (without channel letter), e.g. `Facet2 FabA+`
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Pin layout used for a channel
+    pub fn pin_map(&self, channel: Channel) -> &PinMap {
+        &self.pin_maps[channel.index()]
+    }
+
+    /// Override the pin layout of a channel, for board revisions with different wiring
+    pub fn with_pin_map(mut self, channel: Channel, pin_map: PinMap) -> Self {
+        self.pin_maps[channel.index()] = pin_map;
+        self
+    }
+
+    /// Borrow a raw channel of the FT4232H
+    ///
+    /// Fails with [`ErrorKind::ChannelUnavailable`] if the channel was taken
+    /// or could not be opened, [`Facet2Board::take_channel`] reports why.
+    pub fn channel(&mut self, channel: Channel) -> Result<&mut Ft4232h, Error> {
+        self.channels[channel.index()]
+            .as_mut()
+            .map_err(|_| ErrorKind::ChannelUnavailable { channel }.into())
+    }
+
+    /// Take a raw channel of the FT4232H out of the board
+    ///
+    /// Fails with the error of opening the channel, or with
+    /// [`ErrorKind::ChannelUnavailable`] once it was taken.
+    pub fn take_channel(&mut self, channel: Channel) -> Result<Ft4232h, Error> {
+        core::mem::replace(
+            &mut self.channels[channel.index()],
+            Err(ErrorKind::ChannelUnavailable { channel }.into()),
+        )
+    }
+
+    /// Release all channels, indexed like [`Channel::ALL`]
+    pub fn into_parts(self) -> [Result<Ft4232h, Error>; 4] {
+        self.channels
+    }
+
+    /// eMMC SPI backend on channel A
+    pub fn emmc(&mut self) -> Result<FtdiBackend, Error> {
+        let dev = self.take_channel(Channel::A)?;
+        FtdiBackend::with_pin_map(dev, *self.pin_map(Channel::A))
+    }
+
+    /// SB JTAG on channel B
+    pub fn jtag(&mut self) -> Result<Facet2Jtag, Error> {
+        let dev = self.take_channel(Channel::B)?;
+        Facet2Jtag::new(dev, *self.pin_map(Channel::B))
+    }
+
+    /// Bitbang I2C master on channel C
+    ///
+    /// Channel C has no MPSSE, so [`I2cFtMpsse`](crate::i2c::i2c_mpsse::I2cFtMpsse) cannot be used here.
+    pub fn i2c(&mut self) -> Result<I2cFtBitbang, Error> {
+        let dev = self.take_channel(Channel::C)?;
+        I2cFtBitbang::with_pin_map(dev, *self.pin_map(Channel::C))
+    }
+
+    /// SMC UART on channel D
+    pub fn uart(&mut self) -> Result<Facet2Uart, Error> {
+        let dev = self.take_channel(Channel::D)?;
+        Facet2Uart::new(dev)
+    }
+}
+
+/// SMC UART on channel D
+pub struct Facet2Uart {
+    dev: Ft4232h,
+}
+
+impl Facet2Uart {
+    fn new(mut dev: Ft4232h) -> Result<Self, Error> {
+        dev.set_bit_mode(0x0, BitMode::Reset)?;
+        dev.set_baud_rate(UART_BAUD_RATE)?;
+        dev.set_timeouts(Duration::from_millis(100), Duration::from_millis(100))?;
+        dev.purge_all()?;
+        Ok(Self { dev })
+    }
+
+    /// Change the baud rate (default: 115200)
+    pub fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), Error> {
+        self.dev.set_baud_rate(baud_rate)?;
+        Ok(())
+    }
+
+    /// Release the underlying channel
+    pub fn into_inner(self) -> Ft4232h {
+        self.dev
+    }
+}
+
+impl io::Read for Facet2Uart {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.dev.read(buf).map_err(io::Error::other)
+    }
+}
+
+impl io::Write for Facet2Uart {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.dev.write(buf).map_err(io::Error::other)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// SB JTAG on channel B
+///
+/// The channel is put into MPSSE mode with the same initial layout the OpenOCD
+/// config (`configs/jtag/ft4232h_facet.cfg`) uses. Scan chain handling is left
+/// to the user of [`Facet2Jtag::into_inner`].
+pub struct Facet2Jtag {
+    dev: Ft4232h,
+    pins: PinMap,
+    gpio_state: u8,
+}
+
+impl Facet2Jtag {
+    fn new(mut dev: Ft4232h, pins: PinMap) -> Result<Self, Error> {
+        for signal in [
+            Signal::JtagTck,
+            Signal::JtagTdi,
+            Signal::JtagTdo,
+            Signal::JtagTms,
+        ] {
+            pins.require(signal)?;
+        }
+
+        dev.set_bit_mode(0x0, BitMode::Mpsse)?;
+        let gpio_state = pins.idle_state();
+        dev.set_gpio_lower(gpio_state, pins.output_mask())?;
+
+        Ok(Self {
+            dev,
+            pins,
+            gpio_state,
+        })
+    }
+
+    fn set_signal(&mut self, signal: Signal, high: bool) -> Result<(), Error> {
+        let mask = self.pins.require(signal)?;
+        if high {
+            self.gpio_state |= mask;
+        } else {
+            self.gpio_state &= !mask;
+        }
+        self.dev
+            .set_gpio_lower(self.gpio_state, self.pins.output_mask())?;
+        Ok(())
+    }
+
+    /// Drive JTAG_EN
+    pub fn set_enable(&mut self, enabled: bool) -> Result<(), Error> {
+        self.set_signal(Signal::JtagEn, enabled)
+    }
+
+    /// Drive JTAG_RST_N (active low)
+    pub fn set_reset(&mut self, asserted: bool) -> Result<(), Error> {
+        self.set_signal(Signal::JtagRstN, !asserted)
+    }
+
+    /// Pin layout of the channel
+    pub fn pin_map(&self) -> &PinMap {
+        &self.pins
+    }
+
+    /// Release the underlying channel
+    pub fn into_inner(self) -> Ft4232h {
+        self.dev
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_adapter() {
+        let adapter = AdapterInfo {
+            family: AdapterFamily::Facet2,
+            serial: "FT0001".to_string(),
+            description: "Facet2 FabA+".to_string(),
+            revision: Some("FabA+".to_string()),
+            location: 0,
+            channels: Channel::ALL.to_vec(),
+            in_use: false,
+        };
+
+        let facet = AdapterInfo { family: AdapterFamily::Facet, ..adapter.clone() };
+        assert!(matches!(
+            Facet2Board::from_adapter(&facet).map_err(Error::into_kind),
+            Err(ErrorKind::UnsupportedAdapter { family: AdapterFamily::Facet })
+        ));
+
+        let channels = Channel::ALL.map(|_| Err(ErrorKind::BoardNotFound.into()));
+        let mut board = Facet2Board::with_channels(&adapter, channels)
+            .with_pin_map(Channel::D, PinMap::facet2_i2c());
+        assert_eq!(board.serial(), "FT0001");
+        assert_eq!(board.description(), "Facet2 FabA+");
+        assert_eq!(board.pin_map(Channel::A), &PinMap::facet2_spi());
+        assert_eq!(board.pin_map(Channel::B), &PinMap::facet2_jtag());
+        assert_eq!(board.pin_map(Channel::D), &PinMap::facet2_i2c());
+
+        // The open error is reported once, later requests see the channel as taken
+        assert!(matches!(board.i2c().map_err(Error::into_kind), Err(ErrorKind::BoardNotFound)));
+        assert!(matches!(
+            board.i2c().map_err(Error::into_kind),
+            Err(ErrorKind::ChannelUnavailable { channel: Channel::C })
+        ));
+        assert!(matches!(
+            board.channel(Channel::C).map_err(Error::into_kind),
+            Err(ErrorKind::ChannelUnavailable { channel: Channel::C })
+        ));
+    }
+}
@@ -0,0 +1,9 @@
+//! Board level abstractions
+//!
+//! Boards bundle the FT4232H channels of a debug adapter and hand out
+//! ready-to-use drivers for each of them.
+pub mod discovery;
+pub mod facet2;
+
+pub use discovery::{AdapterFamily, AdapterInfo, list_adapters};
+pub use facet2::{Channel, Facet2Board};
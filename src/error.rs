@@ -1,43 +1,532 @@
 use crate::prelude::*;
+use crate::pinmap::Signal;
+use crate::spi::protocol::commands::Register;
 use thiserror::Error as DeriveError;
 #[cfg(feature = "ftdi")]
 use libftd2xx::{TimeoutError as FtdiTimeout, FtStatus, DeviceTypeError};
 
+/// What went wrong, see [`Error`] for the operation context
 #[derive(DeriveError, Debug)]
-pub enum Error {
-    #[error("Not implemented")]
-    Todo,
-    
+pub enum ErrorKind {
+    #[error("{operation} is not supported")]
+    Unsupported { operation: &'static str },
+
     #[cfg(feature = "ftdi")]
     #[error("FTDI Timeout")]
     DeviceTimeout(#[from] FtdiTimeout),
-    
+
     #[cfg(feature = "ftdi")]
     #[error("FTDI Status: {0}")]
     FtStatus(#[from] FtStatus),
-    
+
     #[cfg(feature = "ftdi")]
     #[error("FTDI Device Type Error: {0}")]
     DeviceTypeError(#[from] DeviceTypeError),
-    
-    #[error("SPI error")]
-    SpiError,
+
+    #[cfg(feature = "std")]
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// Error reported by an `embedded-hal` SPI bus or device
+    #[error("SPI error: {0}")]
+    Spi(embedded_hal::spi::ErrorKind),
+
+    /// Error reported by an `embedded-hal` I2C bus
+    #[error("I2C error: {0}")]
+    I2c(embedded_hal::i2c::ErrorKind),
+
+    /// Error reported by an `embedded-hal` GPIO pin
+    #[error("GPIO error: {0:?}")]
+    Gpio(embedded_hal::digital::ErrorKind),
 
     #[error("Invalid GPIO state")]
     InvalidGpioState,
-    
+
     #[error("Invalid pin mask (must be single bit)")]
     InvalidPinMask,
-    
+
+    #[error("Pin conflict on GPIO line {line}")]
+    PinConflict { line: u8 },
+
+    #[error("Invalid direction for GPIO line {line}")]
+    InvalidPinDirection { line: u8 },
+
+    #[error("Signal cannot be assigned to GPIO line {line}")]
+    InvalidPinAssignment { line: u8 },
+
+    #[error("Pin map is missing signal {0:?}")]
+    MissingPin(Signal),
+
     #[error("Sanity check failed: expected {expected:#X}, got {actual:#X}")]
     SanityCheckFailed { expected: u32, actual: u32 },
-    
+
+    #[error("Board not found")]
+    BoardNotFound,
+
+    #[cfg(feature = "ftdi")]
+    #[error("{family} adapters are not supported here")]
+    UnsupportedAdapter { family: crate::board::AdapterFamily },
+
+    /// Board channel taken by an earlier driver or failed to open
+    #[cfg(feature = "ftdi")]
+    #[error("Channel {channel:?} is not available")]
+    ChannelUnavailable { channel: crate::board::Channel },
+
     #[error("Device initialization failed")]
     InitializationFailed,
-    
-    #[error("Register read/write failed")]
-    RegisterAccessFailed,
-    
+
+    #[error("Invalid response register index {index}")]
+    InvalidResponseIndex { index: u8 },
+
+    #[error("Unsupported clock: {clock_hz} Hz")]
+    UnsupportedClock { clock_hz: u32 },
+
+    #[error("Unsupported number of turnaround cycles: {cycles}")]
+    UnsupportedTurnaround { cycles: u16 },
+
+    #[error("Seek out of range")]
+    SeekOutOfRange,
+
+    #[error("Access out of bounds")]
+    OutOfBounds,
+
+    #[error("Access not aligned to the erase or write size")]
+    NotAligned,
+
+    #[error("Read-back mismatch at address {address:#X}")]
+    VerifyMismatch { address: u32 },
+
+    /// Status prefix of a flash read that no driven bus produces, all zeros or all ones
+    #[error("Implausible read status {status:#06X}")]
+    InvalidReadStatus { status: u16 },
+
+    #[error("Formatting output failed")]
+    Format,
+
+    /// A bus line stays low, SCL beyond the clock stretch timeout or SDA after recovery
+    #[error("I2C bus stuck: {line:?} held low")]
+    BusStuck { line: Signal },
+
+    #[error("Invalid WAV file: {reason}")]
+    InvalidWav { reason: &'static str },
+
     #[error("Operation timed out")]
     Timeout,
+
+    #[error("Timed out polling {register:?} after {elapsed:?}, last value {last_value:#010X}")]
+    PollTimeout {
+        register: Register,
+        last_value: u32,
+        elapsed: Duration,
+    },
+}
+
+impl ErrorKind {
+    /// Whether retrying the same operation may succeed
+    ///
+    /// Timeouts, NACKs, lost arbitration and failed sanity checks are
+    /// usually caused by a busy device or marginal signal integrity.
+    /// Everything else (invalid arguments, missing hardware, unsupported
+    /// operations) fails again the same way.
+    pub fn is_transient(&self) -> bool {
+        use embedded_hal::i2c::ErrorKind as I2cKind;
+
+        match self {
+            #[cfg(feature = "ftdi")]
+            ErrorKind::DeviceTimeout(_) => true,
+            #[cfg(feature = "std")]
+            ErrorKind::Io(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::Interrupted
+                    | std::io::ErrorKind::WouldBlock
+            ),
+            ErrorKind::I2c(kind) => matches!(
+                kind,
+                I2cKind::NoAcknowledge(_) | I2cKind::ArbitrationLoss | I2cKind::Bus
+            ),
+            ErrorKind::Spi(kind) => matches!(kind, embedded_hal::spi::ErrorKind::Overrun),
+            ErrorKind::SanityCheckFailed { .. }
+            | ErrorKind::VerifyMismatch { .. }
+            | ErrorKind::InvalidReadStatus { .. }
+            | ErrorKind::Timeout
+            | ErrorKind::PollTimeout { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+/// Where an error happened
+///
+/// Fields are filled in while the error propagates, the innermost value wins.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// Register address being accessed
+    pub register: Option<u8>,
+    /// eMMC page being transferred
+    pub page: Option<u32>,
+    /// eMMC command index, e.g. 17 for READ_SINGLE_BLOCK
+    pub command: Option<u8>,
+}
+
+impl ErrorContext {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl core::fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut separator = "";
+        if let Some(command) = self.command {
+            write!(f, "CMD{command}")?;
+            separator = ", ";
+        }
+        if let Some(page) = self.page {
+            write!(f, "{separator}page {page}")?;
+            separator = ", ";
+        }
+        if let Some(register) = self.register {
+            write!(f, "{separator}register {register:#04x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Bounds an `embedded-hal` error needs to be kept as the [`Error::source`](core::error::Error::source)
+///
+/// Without `std` there is nowhere to keep it, only its kind is recorded.
+#[cfg(feature = "std")]
+pub trait SourceError: core::fmt::Debug + Send + Sync + 'static {}
+#[cfg(feature = "std")]
+impl<T: core::fmt::Debug + Send + Sync + 'static> SourceError for T {}
+
+/// Bounds an `embedded-hal` error needs to be kept as the [`Error::source`](core::error::Error::source)
+///
+/// Without `std` there is nowhere to keep it, only its kind is recorded.
+#[cfg(not(feature = "std"))]
+pub trait SourceError {}
+#[cfg(not(feature = "std"))]
+impl<T> SourceError for T {}
+
+/// An `embedded-hal` error, which only has to implement `Debug`
+#[cfg(feature = "std")]
+#[derive(Debug)]
+struct HalError<E>(E);
+
+#[cfg(feature = "std")]
+impl<E: core::fmt::Debug> core::fmt::Display for HalError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: core::fmt::Debug> core::error::Error for HalError<E> {}
+
+/// Error type of the crate: an [`ErrorKind`] plus the [`ErrorContext`] it occurred in
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    context: ErrorContext,
+    /// Original error of a pin or bus, see [`Error::gpio`]
+    #[cfg(feature = "std")]
+    source: Option<Box<dyn core::error::Error + Send + Sync>>,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            context: ErrorContext::default(),
+            #[cfg(feature = "std")]
+            source: None,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn with_source<E: SourceError>(mut self, error: E) -> Self {
+        self.source = Some(Box::new(HalError(error)));
+        self
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn with_source<E>(self, _error: E) -> Self {
+        self
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    pub fn into_kind(self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn context(&self) -> ErrorContext {
+        self.context
+    }
+
+    /// See [`ErrorKind::is_transient`]
+    pub fn is_transient(&self) -> bool {
+        self.kind.is_transient()
+    }
+
+    /// Record the register being accessed, unless already known
+    pub fn with_register(mut self, register: u8) -> Self {
+        self.context.register.get_or_insert(register);
+        self
+    }
+
+    /// Record the eMMC page being transferred, unless already known
+    pub fn with_page(mut self, page: u32) -> Self {
+        self.context.page.get_or_insert(page);
+        self
+    }
+
+    /// Record the eMMC command index, unless already known
+    pub fn with_command(mut self, command: u8) -> Self {
+        self.context.command.get_or_insert(command);
+        self
+    }
+
+    /// Keep an `embedded-hal` SPI error, its kind decides [`ErrorKind::Spi`]
+    pub fn spi<E: embedded_hal::spi::Error + SourceError>(error: E) -> Self {
+        Error::from(ErrorKind::Spi(error.kind())).with_source(error)
+    }
+
+    /// Keep an `embedded-hal` I2C error, its kind decides [`ErrorKind::I2c`]
+    pub fn i2c<E: embedded_hal::i2c::Error + SourceError>(error: E) -> Self {
+        Error::from(ErrorKind::I2c(error.kind())).with_source(error)
+    }
+
+    /// Keep an `embedded-hal` GPIO error, its kind decides [`ErrorKind::Gpio`]
+    pub fn gpio<E: embedded_hal::digital::Error + SourceError>(error: E) -> Self {
+        Error::from(ErrorKind::Gpio(error.kind())).with_source(error)
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if !self.context.is_empty() {
+            write!(f, " ({})", self.context)?;
+        }
+        Ok(())
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        #[cfg(feature = "std")]
+        if let Some(source) = &self.source {
+            return Some(source.as_ref());
+        }
+        core::error::Error::source(&self.kind)
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Self::new(kind)
+    }
+}
+
+/// Conversions for errors that `?` should accept directly
+macro_rules! error_from {
+    ($($(#[$meta:meta])* $source:ty => $variant:ident,)*) => {
+        $(
+            $(#[$meta])*
+            impl From<$source> for Error {
+                fn from(error: $source) -> Self {
+                    ErrorKind::$variant(error.into()).into()
+                }
+            }
+        )*
+    };
+}
+
+error_from! {
+    #[cfg(feature = "ftdi")]
+    FtdiTimeout => DeviceTimeout,
+    #[cfg(feature = "ftdi")]
+    FtStatus => FtStatus,
+    #[cfg(feature = "ftdi")]
+    DeviceTypeError => DeviceTypeError,
+    #[cfg(feature = "std")]
+    std::io::Error => Io,
+    embedded_hal::spi::ErrorKind => Spi,
+    embedded_hal::i2c::ErrorKind => I2c,
+    embedded_hal::digital::ErrorKind => Gpio,
+}
+
+#[cfg(feature = "std")]
+impl From<Error> for std::io::Error {
+    fn from(error: Error) -> Self {
+        use std::io::ErrorKind as IoKind;
+
+        let kind = match &error.kind {
+            ErrorKind::Io(e) => e.kind(),
+            ErrorKind::Unsupported { .. } => IoKind::Unsupported,
+            ErrorKind::BoardNotFound => IoKind::NotFound,
+            #[cfg(feature = "ftdi")]
+            ErrorKind::ChannelUnavailable { .. } => IoKind::ResourceBusy,
+            #[cfg(feature = "ftdi")]
+            ErrorKind::UnsupportedAdapter { .. } => IoKind::Unsupported,
+            ErrorKind::SeekOutOfRange
+            | ErrorKind::OutOfBounds
+            | ErrorKind::NotAligned
+            | ErrorKind::InvalidPinMask
+            | ErrorKind::PinConflict { .. }
+            | ErrorKind::InvalidPinDirection { .. }
+            | ErrorKind::InvalidPinAssignment { .. }
+            | ErrorKind::MissingPin(_)
+            | ErrorKind::InvalidResponseIndex { .. }
+            | ErrorKind::UnsupportedClock { .. }
+            | ErrorKind::UnsupportedTurnaround { .. } => IoKind::InvalidInput,
+            #[cfg(feature = "ftdi")]
+            ErrorKind::DeviceTimeout(_) => IoKind::TimedOut,
+            ErrorKind::Timeout | ErrorKind::PollTimeout { .. } => IoKind::TimedOut,
+            ErrorKind::VerifyMismatch { .. }
+            | ErrorKind::InvalidReadStatus { .. }
+            | ErrorKind::InvalidWav { .. } => IoKind::InvalidData,
+            _ => IoKind::Other,
+        };
+        std::io::Error::new(kind, error)
+    }
+}
+
+impl embedded_io::Error for Error {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        use embedded_io::ErrorKind as IoKind;
+
+        match &self.kind {
+            ErrorKind::Unsupported { .. } => IoKind::Unsupported,
+            ErrorKind::BoardNotFound => IoKind::NotFound,
+            ErrorKind::SeekOutOfRange | ErrorKind::OutOfBounds | ErrorKind::NotAligned => {
+                IoKind::InvalidInput
+            }
+            ErrorKind::Timeout | ErrorKind::PollTimeout { .. } => IoKind::TimedOut,
+            _ => IoKind::Other,
+        }
+    }
+}
+
+impl embedded_storage::nor_flash::NorFlashError for Error {
+    fn kind(&self) -> embedded_storage::nor_flash::NorFlashErrorKind {
+        use embedded_storage::nor_flash::NorFlashErrorKind;
+
+        match self.kind {
+            ErrorKind::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+            ErrorKind::NotAligned => NorFlashErrorKind::NotAligned,
+            _ => NorFlashErrorKind::Other,
+        }
+    }
+}
+
+/// Range check failures from `embedded_storage::nor_flash::check_*`
+impl From<embedded_storage::nor_flash::NorFlashErrorKind> for Error {
+    fn from(kind: embedded_storage::nor_flash::NorFlashErrorKind) -> Self {
+        use embedded_storage::nor_flash::NorFlashErrorKind;
+
+        match kind {
+            NorFlashErrorKind::NotAligned => ErrorKind::NotAligned.into(),
+            _ => ErrorKind::OutOfBounds.into(),
+        }
+    }
+}
+
+impl embedded_hal::spi::Error for Error {
+    fn kind(&self) -> embedded_hal::spi::ErrorKind {
+        match self.kind {
+            ErrorKind::Spi(kind) => kind,
+            _ => embedded_hal::spi::ErrorKind::Other,
+        }
+    }
+}
+
+impl embedded_hal::i2c::Error for Error {
+    fn kind(&self) -> embedded_hal::i2c::ErrorKind {
+        match self.kind {
+            ErrorKind::I2c(kind) => kind,
+            ErrorKind::BusStuck { .. } => embedded_hal::i2c::ErrorKind::Bus,
+            _ => embedded_hal::i2c::ErrorKind::Other,
+        }
+    }
+}
+
+impl embedded_hal::digital::Error for Error {
+    fn kind(&self) -> embedded_hal::digital::ErrorKind {
+        match self.kind {
+            ErrorKind::Gpio(kind) => kind,
+            _ => embedded_hal::digital::ErrorKind::Other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal::i2c::NoAcknowledgeSource;
+
+    #[test]
+    fn test_context_display() {
+        let error = Error::from(ErrorKind::Timeout)
+            .with_register(0x0C)
+            .with_command(17)
+            .with_page(42)
+            .with_register(0x03);
+        assert_eq!(error.to_string(), "Operation timed out (CMD17, page 42, register 0x0c)");
+        assert_eq!(Error::from(ErrorKind::OutOfBounds).to_string(), "Access out of bounds");
+    }
+
+    #[test]
+    fn test_is_transient() {
+        let nack = embedded_hal::i2c::ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data);
+        assert!(Error::i2c(nack).is_transient());
+        assert!(Error::from(ErrorKind::Timeout).with_page(1).is_transient());
+        assert!(!Error::from(ErrorKind::OutOfBounds).is_transient());
+        assert!(!Error::spi(embedded_hal::spi::ErrorKind::ModeFault).is_transient());
+    }
+
+    #[test]
+    fn test_hal_kinds() {
+        let nack = embedded_hal::i2c::ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address);
+        let error = Error::i2c(nack).with_register(0x0C);
+        assert_eq!(embedded_hal::i2c::Error::kind(&error), nack);
+        assert_eq!(
+            embedded_hal::spi::Error::kind(&error),
+            embedded_hal::spi::ErrorKind::Other
+        );
+    }
+
+    #[test]
+    fn test_hal_source() {
+        use core::error::Error as _;
+
+        #[derive(Debug)]
+        struct PinFault;
+
+        impl embedded_hal::digital::Error for PinFault {
+            fn kind(&self) -> embedded_hal::digital::ErrorKind {
+                embedded_hal::digital::ErrorKind::Other
+            }
+        }
+
+        let error = Error::gpio(PinFault);
+        assert!(matches!(error.kind(), ErrorKind::Gpio(_)));
+        assert_eq!(error.source().unwrap().to_string(), "PinFault");
+        assert!(Error::from(ErrorKind::Timeout).source().is_none());
+    }
+
+    #[test]
+    fn test_io_conversion() {
+        let io_error = std::io::Error::from(Error::from(ErrorKind::SeekOutOfRange).with_page(3));
+        assert_eq!(io_error.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(io_error.to_string(), "Seek out of range (page 3)");
+
+        let error = Error::from(std::io::Error::from(std::io::ErrorKind::TimedOut));
+        assert!(error.is_transient());
+        assert_eq!(std::io::Error::from(error).kind(), std::io::ErrorKind::TimedOut);
+    }
 }
@@ -0,0 +1,29 @@
+//! Test doubles shared by the unit tests
+
+use core::convert::Infallible;
+
+use embedded_hal::{delay::DelayNs, digital::{ErrorType, OutputPin}};
+
+/// Output pin that is not connected
+pub struct NoPin;
+
+impl ErrorType for NoPin {
+    type Error = Infallible;
+}
+
+impl OutputPin for NoPin {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+/// Delay provider returning immediately
+pub struct NoDelay;
+
+impl DelayNs for NoDelay {
+    fn delay_ns(&mut self, _ns: u32) {}
+}
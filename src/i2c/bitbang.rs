@@ -0,0 +1,1009 @@
+//! GPIO bit-banged I2C master
+//!
+//! [`BitbangI2c`] drives SCL and SDA through `embedded_hal::digital` pins,
+//! so the same driver runs on microcontroller GPIOs, GPIO expanders and the
+//! FTDI sync bitbang mode ([`I2cFtBitbang`](super::i2c_bitbang::I2cFtBitbang)).
+//!
+//! Both lines are open-drain: `set_low` pulls a line low, `set_high` releases
+//! it to the pull-up, and `is_high` reads the actual level of the bus. Pins
+//! configured as push-pull outputs must not be used, they would short
+//! against a slave holding the line low.
+
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{InputPin, OutputPin},
+    i2c::{ErrorKind as I2cErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation},
+};
+
+use crate::prelude::*;
+use crate::error::{Error, ErrorKind, SourceError};
+use crate::pinmap::Signal;
+use crate::spi::poll::{DefaultClock, PollClock};
+
+/// How long a slave may hold SCL low before the bus counts as stuck
+pub const DEFAULT_STRETCH_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// SCL pulses clocked by [`BitbangI2c::recover_bus`], enough to finish any byte
+const RECOVERY_PULSES: usize = 9;
+
+/// Bus timing of [`BitbangI2c`]
+///
+/// The delays are minimums, the actual clock is further slowed down by how
+/// fast the pins toggle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct I2cTiming {
+    /// SCL high period, also the setup and hold time of START and STOP
+    pub high_ns: u32,
+    /// SCL low period, SDA changes in its middle
+    pub low_ns: u32,
+    /// How long a slave may stretch the clock
+    pub stretch_timeout: Duration,
+    /// Delay between SCL reads while the clock is stretched
+    ///
+    /// The stretch time is taken from the master's [`PollClock`]. Without a
+    /// clock it is the sum of these delays and slow pin reads extend the
+    /// timeout.
+    pub stretch_poll_ns: u32,
+}
+
+impl I2cTiming {
+    /// Standard mode, 100 kHz
+    pub const STANDARD_MODE: Self = Self {
+        high_ns: 4_000,
+        low_ns: 4_700,
+        stretch_timeout: DEFAULT_STRETCH_TIMEOUT,
+        stretch_poll_ns: 10_000,
+    };
+
+    /// Fast mode, 400 kHz
+    pub const FAST_MODE: Self = Self {
+        high_ns: 600,
+        low_ns: 1_300,
+        ..Self::STANDARD_MODE
+    };
+}
+
+impl Default for I2cTiming {
+    fn default() -> Self {
+        Self::STANDARD_MODE
+    }
+}
+
+/// Bit-banged I2C master
+///
+/// * `SDA` – Open-drain data line
+/// * `SCL` – Open-drain clock line
+/// * `D`   – Delay provider
+/// * `C`   – Clock for the stretch timeout, [`DefaultClock`] unless set
+///
+/// Follows the `embedded-hal` transaction contract: adjacent operations in
+/// the same direction are merged, a repeated START is only sent when the
+/// direction changes and a STOP ends every transaction, also failed ones,
+/// unless arbitration was lost to another master.
+///
+/// Slaves may stretch the clock: after releasing SCL the master waits until
+/// it reads high, for up to [`I2cTiming::stretch_timeout`].
+pub struct BitbangI2c<SDA, SCL, D, C = DefaultClock> {
+    sda: SDA,
+    scl: SCL,
+    delay: D,
+    clock: C,
+    timing: I2cTiming,
+}
+
+impl<SDA, SCL, D> BitbangI2c<SDA, SCL, D>
+where
+    SDA: InputPin + OutputPin<Error: SourceError>,
+    SCL: InputPin + OutputPin<Error: SourceError>,
+    D: DelayNs,
+{
+    /// Create a new bit-banged I2C master with [`I2cTiming::STANDARD_MODE`]
+    pub fn with_pins(sda: SDA, scl: SCL, delay: D) -> Self {
+        Self {
+            sda,
+            scl,
+            delay,
+            clock: DefaultClock::default(),
+            timing: I2cTiming::default(),
+        }
+    }
+}
+
+impl<SDA, SCL, D, C> BitbangI2c<SDA, SCL, D, C>
+where
+    SDA: InputPin + OutputPin<Error: SourceError>,
+    SCL: InputPin + OutputPin<Error: SourceError>,
+    D: DelayNs,
+    C: PollClock,
+{
+    /// Measure the stretch timeout with another clock
+    pub fn with_clock<C2: PollClock>(self, clock: C2) -> BitbangI2c<SDA, SCL, D, C2> {
+        BitbangI2c {
+            sda: self.sda,
+            scl: self.scl,
+            delay: self.delay,
+            clock,
+            timing: self.timing,
+        }
+    }
+
+    /// Change the bus timing
+    pub fn with_timing(mut self, timing: I2cTiming) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// Change how long a slave may stretch the clock
+    pub fn with_stretch_timeout(mut self, timeout: Duration) -> Self {
+        self.timing.stretch_timeout = timeout;
+        self
+    }
+
+    pub fn timing(&self) -> I2cTiming {
+        self.timing
+    }
+
+    /// Release the pins and delay provider
+    pub fn release(self) -> (SDA, SCL, D) {
+        (self.sda, self.scl, self.delay)
+    }
+
+    fn sda_high(&mut self) -> Result<(), Error> {
+        self.sda.set_high().map_err(Error::gpio)
+    }
+
+    fn sda_low(&mut self) -> Result<(), Error> {
+        self.sda.set_low().map_err(Error::gpio)
+    }
+
+    fn sda_is_high(&mut self) -> Result<bool, Error> {
+        self.sda.is_high().map_err(Error::gpio)
+    }
+
+    fn scl_low(&mut self) -> Result<(), Error> {
+        self.scl.set_low().map_err(Error::gpio)
+    }
+
+    /// Release SCL and wait while a slave stretches the clock
+    fn scl_high(&mut self) -> Result<(), Error> {
+        self.scl.set_high().map_err(Error::gpio)?;
+
+        let start = self.clock.now();
+        let mut waited = Duration::ZERO;
+        while self.scl.is_low().map_err(Error::gpio)? {
+            if self.clock.now().saturating_sub(start).max(waited) >= self.timing.stretch_timeout {
+                return Err(ErrorKind::BusStuck { line: Signal::I2cScl }.into());
+            }
+            self.delay.delay_ns(self.timing.stretch_poll_ns);
+            waited += Duration::from_nanos(self.timing.stretch_poll_ns.max(1).into());
+        }
+        Ok(())
+    }
+
+    /// Release both lines without a STOP, after losing arbitration or a stuck SCL
+    fn release_bus(&mut self) -> Result<(), Error> {
+        self.sda_high()?;
+        self.scl.set_high().map_err(Error::gpio)
+    }
+
+    fn high_delay(&mut self) {
+        self.delay.delay_ns(self.timing.high_ns);
+    }
+
+    fn half_low_delay(&mut self) {
+        self.delay.delay_ns(self.timing.low_ns / 2);
+    }
+
+    /// START, or repeated START when SCL is low
+    ///
+    /// Before the first START a SDA held low by a slave is freed with
+    /// [`BitbangI2c::recover_bus`]. Recovery ends in a STOP, so a repeated
+    /// START finding SDA low fails with [`I2cErrorKind::ArbitrationLoss`].
+    fn i2c_start(&mut self, repeated: bool) -> Result<(), Error> {
+        // SDA descending while SCL is HIGH.
+        self.sda_high()?;
+        self.scl_high()?;
+        if !self.sda_is_high()? {
+            if repeated {
+                return Err(I2cErrorKind::ArbitrationLoss.into());
+            }
+            self.recover_bus()?;
+        }
+        self.high_delay();
+        self.sda_low()?; self.high_delay();
+        self.scl_low()?; self.half_low_delay();
+        Ok(())
+    }
+
+    fn i2c_stop(&mut self) -> Result<(), Error> {
+        // SDA rasing while SCL is HIGH.
+        self.sda_low()?; self.half_low_delay();
+        self.scl_high()?; self.high_delay();
+        self.sda_high()?; self.high_delay();
+        Ok(())
+    }
+
+    /// Clock one bit with SCL high for the sampling, returning the level of SDA
+    fn clock_bit(&mut self) -> Result<bool, Error> {
+        self.scl_high()?; self.high_delay();
+        let bit = self.sda_is_high()?;
+        self.scl_low()?; self.half_low_delay();
+        Ok(bit)
+    }
+
+    /// Send a byte, returning whether the slave acknowledged it
+    ///
+    /// A released SDA that reads low means another master won arbitration.
+    fn i2c_tx(&mut self, byte: u8) -> Result<bool, Error> {
+        for bit in (0..8).rev() {
+            let high = byte >> bit & 1 != 0;
+            if high { self.sda_high()?; } else { self.sda_low()?; }
+            self.half_low_delay();
+            if !self.clock_bit()? && high {
+                return Err(I2cErrorKind::ArbitrationLoss.into());
+            }
+        }
+
+        // Release SDA for ACK, sample it with SCL high
+        self.sda_high()?; self.half_low_delay();
+        Ok(!self.clock_bit()?)
+    }
+
+    fn i2c_rx_byte(&mut self, send_nack: bool) -> Result<u8, Error> {
+        let mut data = 0u8;
+
+        self.sda_high()?; // release SDA
+        for _ in 0..8 {
+            self.half_low_delay();
+            data = data << 1 | u8::from(self.clock_bit()?);
+        }
+
+        // Send ACK/NACK
+        if send_nack { self.sda_high()?; } else { self.sda_low()?; }
+        self.half_low_delay();
+        self.clock_bit()?;
+        self.sda_high()?; // release
+
+        Ok(data)
+    }
+
+    /// Free a bus whose SDA is held low, e.g. by a slave interrupted mid-read
+    ///
+    /// Clocks up to 9 SCL pulses until the slave releases SDA, then sends a
+    /// STOP. Fails with [`ErrorKind::BusStuck`] if SDA stays low.
+    pub fn recover_bus(&mut self) -> Result<(), Error> {
+        self.sda_high()?;
+        self.scl_high()?;
+        for _ in 0..RECOVERY_PULSES {
+            if self.sda_is_high()? {
+                break;
+            }
+            self.scl_low()?; self.half_low_delay();
+            self.scl_high()?; self.high_delay();
+        }
+        if !self.sda_is_high()? {
+            return Err(ErrorKind::BusStuck { line: Signal::I2cSda }.into());
+        }
+
+        self.scl_low()?; self.half_low_delay();
+        self.i2c_stop()
+    }
+
+    /// Send all bytes of adjacent write operations, stopping at the first NACK
+    fn write_group(&mut self, operations: &[Operation<'_>]) -> Result<(), Error> {
+        for op in operations {
+            if let Operation::Write(wr) = op {
+                for &byte in wr.iter() {
+                    if !self.i2c_tx(byte)? {
+                        return Err(I2cErrorKind::NoAcknowledge(NoAcknowledgeSource::Data).into());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fill adjacent read operations, NACKing only the very last byte
+    ///
+    /// The slave owns SDA after acknowledging a read address, so a group
+    /// without any bytes still reads and NACKs one byte to free the bus.
+    fn read_group(&mut self, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        let mut remaining: usize = operations
+            .iter()
+            .map(|op| match op {
+                Operation::Read(rd) => rd.len(),
+                Operation::Write(_) => 0,
+            })
+            .sum();
+        if remaining == 0 {
+            self.i2c_rx_byte(true)?;
+            return Ok(());
+        }
+
+        for op in operations {
+            if let Operation::Read(rd) = op {
+                for byte in rd.iter_mut() {
+                    remaining -= 1;
+                    *byte = self.i2c_rx_byte(remaining == 0)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Everything between the first START and the STOP
+    fn run_transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        let mut start = 0;
+        while start < operations.len() {
+            let read = matches!(operations[start], Operation::Read(_));
+            let len = operations[start..]
+                .iter()
+                .take_while(|op| matches!(op, Operation::Read(_)) == read)
+                .count();
+            let group = &mut operations[start..start + len];
+
+            self.i2c_start(start > 0)?;
+            if !self.i2c_tx(address << 1 | u8::from(read))? {
+                return Err(I2cErrorKind::NoAcknowledge(NoAcknowledgeSource::Address).into());
+            }
+            if read {
+                self.read_group(group)?;
+            } else {
+                self.write_group(group)?;
+            }
+            start += len;
+        }
+        Ok(())
+    }
+}
+
+impl<SDA, SCL, D, C> I2c for BitbangI2c<SDA, SCL, D, C>
+where
+    SDA: InputPin + OutputPin<Error: SourceError>,
+    SCL: InputPin + OutputPin<Error: SourceError>,
+    D: DelayNs,
+    C: PollClock,
+{
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        if operations.is_empty() {
+            return Ok(());
+        }
+
+        let result = self.run_transaction(address, operations);
+        // Release the bus after errors as well. The winner of an arbitration
+        // owns it, and a STOP would wait for a stuck SCL all over again.
+        let stop = match &result {
+            Err(e) if matches!(
+                e.kind(),
+                ErrorKind::I2c(I2cErrorKind::ArbitrationLoss) | ErrorKind::BusStuck { line: Signal::I2cScl }
+            ) => self.release_bus(),
+            _ => self.i2c_stop(),
+        };
+        result.and(stop)
+    }
+}
+
+impl<SDA, SCL, D, C> ErrorType for BitbangI2c<SDA, SCL, D, C> {
+    type Error = Error;
+}
+
+#[cfg(feature = "std")]
+pub use port::{GpioPort, PortPin};
+
+#[cfg(feature = "std")]
+mod port {
+    use core::ops::{Deref, DerefMut};
+    use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+    use embedded_hal::digital::{ErrorType, InputPin, OutputPin};
+
+    use crate::error::Error;
+
+    /// Pin-level access to an 8-bit GPIO port, e.g. a GPIO expander
+    ///
+    /// Lines are emulated open-drain: a line is driven low by setting its
+    /// `direction` bit with a 0 value, and released to the pull-up by clearing
+    /// its `direction` bit.
+    pub trait GpioPort {
+        fn set_pins(&mut self, values: u8, direction: u8) -> Result<(), Error>;
+
+        /// Current level of all lines
+        fn read_pins(&mut self) -> Result<u8, Error>;
+    }
+
+    struct SharedPort<P> {
+        port: P,
+        values: u8,
+        direction: u8,
+    }
+
+    /// Open-drain line of a [`GpioPort`] shared with other lines of the port
+    ///
+    /// The port sits behind a mutex, so the pins and a master built on them
+    /// can be moved to another thread.
+    pub struct PortPin<P> {
+        shared: Arc<Mutex<SharedPort<P>>>,
+        mask: u8,
+    }
+
+    /// Exclusive access to the port of a [`PortPin`]
+    pub(crate) struct PortGuard<'a, P>(MutexGuard<'a, SharedPort<P>>);
+
+    impl<P> Deref for PortGuard<'_, P> {
+        type Target = P;
+
+        fn deref(&self) -> &P {
+            &self.0.port
+        }
+    }
+
+    impl<P> DerefMut for PortGuard<'_, P> {
+        fn deref_mut(&mut self) -> &mut P {
+            &mut self.0.port
+        }
+    }
+
+    impl<P: GpioPort> PortPin<P> {
+        /// Split off two lines, `a` and `b` are single-bit masks
+        ///
+        /// Both lines start released.
+        pub fn split(port: P, a: u8, b: u8) -> (Self, Self) {
+            let shared = Arc::new(Mutex::new(SharedPort {
+                port,
+                values: a | b,
+                direction: 0,
+            }));
+            (
+                Self { shared: shared.clone(), mask: a },
+                Self { shared, mask: b },
+            )
+        }
+
+        pub(crate) fn port(&self) -> PortGuard<'_, P> {
+            PortGuard(self.lock())
+        }
+
+        /// Lock the port, a poisoned lock is taken over as every access rewrites the pins
+        fn lock(&self) -> MutexGuard<'_, SharedPort<P>> {
+            self.shared.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+
+        /// Drive the line low or release it
+        fn set(&mut self, released: bool) -> Result<(), Error> {
+            let shared = &mut *self.lock();
+            if released {
+                shared.values |= self.mask;
+                shared.direction &= !self.mask; // input
+            } else {
+                shared.values &= !self.mask;
+                shared.direction |= self.mask; // output
+            }
+            shared.port.set_pins(shared.values, shared.direction)
+        }
+    }
+
+    impl<P> ErrorType for PortPin<P> {
+        type Error = Error;
+    }
+
+    impl<P: GpioPort> OutputPin for PortPin<P> {
+        fn set_low(&mut self) -> Result<(), Error> {
+            self.set(false)
+        }
+
+        fn set_high(&mut self) -> Result<(), Error> {
+            self.set(true)
+        }
+    }
+
+    impl<P: GpioPort> InputPin for PortPin<P> {
+        fn is_high(&mut self) -> Result<bool, Error> {
+            Ok(self.lock().port.read_pins()? & self.mask != 0)
+        }
+
+        fn is_low(&mut self) -> Result<bool, Error> {
+            Ok(!self.is_high()?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::{Cell, RefCell};
+    use core::convert::Infallible;
+    use std::rc::Rc;
+
+    use embedded_hal::digital::ErrorType as PinErrorType;
+
+    use crate::fixtures::NoDelay;
+    use crate::spi::poll::NoClock;
+
+    const SCL: u8 = 1 << 0;
+    const SDA: u8 = 1 << 1;
+
+    /// What the simulated slave saw on the bus
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Event {
+        Start,
+        Stop,
+        /// Byte received by the slave and whether it acknowledged it
+        Write(u8, bool),
+        /// Byte sent by the slave and whether the master acknowledged it
+        Read(u8, bool),
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Phase {
+        Idle,
+        Receive { address: bool },
+        /// Slave pulls SDA low for the ACK clock
+        AckOut { transmit: bool },
+        Transmit,
+        AckIn,
+    }
+
+    /// Open-drain bus with a slave that logs every event
+    struct SimBus {
+        address: u8,
+        /// Data bytes acknowledged before the slave NACKs
+        accept: usize,
+        /// Bytes returned on reads, counting up from here
+        next_read: u8,
+        /// Pin reads for which the slave holds SCL low after every release
+        stretch: usize,
+        /// Pin reads left until the current stretch ends
+        stretching: usize,
+        /// Address byte sent by a second master starting at the same time
+        contender: Option<u8>,
+        contender_sda: bool,
+        /// SDA shorted to ground
+        sda_stuck: bool,
+        /// Bytes written, address included, until the slave keeps its ACK on SDA
+        hold_after: Option<usize>,
+        master_scl: bool,
+        master_sda: bool,
+        slave_sda: bool,
+        phase: Phase,
+        shift: u8,
+        bits: u8,
+        log: Vec<Event>,
+    }
+
+    impl SimBus {
+        fn new(address: u8) -> Self {
+            Self {
+                address,
+                accept: usize::MAX,
+                next_read: 0x10,
+                stretch: 0,
+                stretching: 0,
+                contender: None,
+                contender_sda: true,
+                sda_stuck: false,
+                hold_after: None,
+                master_scl: true,
+                master_sda: true,
+                slave_sda: true,
+                phase: Phase::Idle,
+                shift: 0,
+                bits: 0,
+                log: Vec::new(),
+            }
+        }
+
+        fn sda(&self) -> bool {
+            self.master_sda && self.slave_sda && self.contender_sda && !self.sda_stuck
+        }
+
+        fn scl(&self) -> bool {
+            self.master_scl && self.stretching == 0
+        }
+
+        fn scl_rise(&mut self) {
+            match self.phase {
+                Phase::Receive { .. } => {
+                    self.shift = self.shift << 1 | u8::from(self.sda());
+                    self.bits += 1;
+                }
+                Phase::Transmit => self.bits += 1,
+                Phase::AckIn => {
+                    let acked = !self.sda();
+                    self.log.push(Event::Read(self.shift, acked));
+                    if !acked {
+                        self.phase = Phase::Idle;
+                    }
+                }
+                Phase::Idle | Phase::AckOut { .. } => {}
+            }
+        }
+
+        fn scl_fall(&mut self) {
+            if let (Some(byte), Phase::Receive { address: true }) = (self.contender, self.phase) {
+                self.contender_sda = self.bits == 8 || (byte << self.bits) & 0x80 != 0;
+            }
+            match self.phase {
+                Phase::Receive { address } if self.bits == 8 => {
+                    let acked = if address {
+                        self.shift >> 1 == self.address
+                    } else if self.accept > 0 {
+                        self.accept -= 1;
+                        true
+                    } else {
+                        false
+                    };
+                    self.log.push(Event::Write(self.shift, acked));
+                    if acked {
+                        self.slave_sda = false;
+                        self.phase = Phase::AckOut { transmit: address && self.shift & 1 != 0 };
+                    } else {
+                        self.phase = Phase::Idle;
+                    }
+                }
+                Phase::AckOut { transmit: false } if self.hold_after == Some(1) => self.phase = Phase::Idle,
+                Phase::AckOut { transmit: false } => {
+                    self.hold_after = self.hold_after.map(|n| n - 1);
+                    self.slave_sda = true;
+                    self.phase = Phase::Receive { address: false };
+                    self.shift = 0;
+                    self.bits = 0;
+                }
+                Phase::AckOut { transmit: true } | Phase::AckIn => self.load_next(),
+                Phase::Transmit if self.bits < 8 => self.slave_sda = (self.shift << self.bits) & 0x80 != 0,
+                Phase::Transmit => {
+                    self.slave_sda = true;
+                    self.phase = Phase::AckIn;
+                }
+                Phase::Idle | Phase::Receive { .. } => {}
+            }
+        }
+
+        /// Put the first bit of the next read byte on SDA
+        fn load_next(&mut self) {
+            self.shift = self.next_read;
+            self.next_read = self.next_read.wrapping_add(1);
+            self.bits = 0;
+            self.slave_sda = self.shift & 0x80 != 0;
+            self.phase = Phase::Transmit;
+        }
+    }
+
+    impl GpioPort for SimBus {
+        fn set_pins(&mut self, values: u8, direction: u8) -> Result<(), Error> {
+            let (scl, sda) = (self.scl(), self.sda());
+            self.master_scl = direction & SCL == 0 || values & SCL != 0;
+            self.master_sda = direction & SDA == 0 || values & SDA != 0;
+            if !scl && self.master_scl {
+                self.stretching = self.stretch;
+            }
+
+            if scl && self.scl() && sda != self.sda() {
+                if self.sda() {
+                    self.log.push(Event::Stop);
+                    self.phase = Phase::Idle;
+                } else {
+                    self.log.push(Event::Start);
+                    self.phase = Phase::Receive { address: true };
+                    self.shift = 0;
+                    self.bits = 0;
+                }
+                self.slave_sda = true;
+            } else if !scl && self.scl() {
+                self.scl_rise();
+            } else if scl && !self.scl() {
+                self.scl_fall();
+            }
+            Ok(())
+        }
+
+        fn read_pins(&mut self) -> Result<u8, Error> {
+            if self.stretching > 0 {
+                self.stretching -= 1;
+                if self.scl() {
+                    self.scl_rise();
+                }
+            }
+            Ok(if self.scl() { SCL } else { 0 } | if self.sda() { SDA } else { 0 })
+        }
+    }
+
+    const ADDR: u8 = 0x5A;
+
+    type Master = BitbangI2c<PortPin<SimBus>, PortPin<SimBus>, NoDelay>;
+
+    fn master() -> Master {
+        let (sda, scl) = PortPin::split(SimBus::new(ADDR), SDA, SCL);
+        BitbangI2c::with_pins(sda, scl, NoDelay)
+    }
+
+    #[test]
+    fn test_write_read_repeated_start() {
+        let mut i2c = master();
+        let mut buf = [0u8; 2];
+        i2c.write_read(ADDR, &[0xC1, 0x0C], &mut buf).unwrap();
+
+        assert_eq!(buf, [0x10, 0x11]);
+        assert_eq!(i2c.sda.port().log, [
+            Event::Start,
+            Event::Write(ADDR << 1, true),
+            Event::Write(0xC1, true),
+            Event::Write(0x0C, true),
+            Event::Start,
+            Event::Write(ADDR << 1 | 1, true),
+            Event::Read(0x10, true),
+            Event::Read(0x11, false),
+            Event::Stop,
+        ]);
+    }
+
+    #[test]
+    fn test_merge_same_direction() {
+        let mut i2c = master();
+        let (mut a, mut b) = ([0u8; 1], [0u8; 2]);
+        i2c.transaction(ADDR, &mut [
+            Operation::Write(&[1, 2]),
+            Operation::Write(&[]),
+            Operation::Write(&[3]),
+            Operation::Read(&mut a),
+            Operation::Read(&mut b),
+        ]).unwrap();
+
+        assert_eq!((a, b), ([0x10], [0x11, 0x12]));
+        assert_eq!(i2c.sda.port().log, [
+            Event::Start,
+            Event::Write(ADDR << 1, true),
+            Event::Write(1, true),
+            Event::Write(2, true),
+            Event::Write(3, true),
+            Event::Start,
+            Event::Write(ADDR << 1 | 1, true),
+            Event::Read(0x10, true),
+            Event::Read(0x11, true),
+            Event::Read(0x12, false),
+            Event::Stop,
+        ]);
+    }
+
+    #[test]
+    fn test_nack() {
+        let mut i2c = master();
+        let err = i2c.write(0x10, &[1]).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::I2c(I2cErrorKind::NoAcknowledge(NoAcknowledgeSource::Address))));
+        assert_eq!(i2c.sda.port().log, [Event::Start, Event::Write(0x10 << 1, false), Event::Stop]);
+
+        // The transfer ends at the first NACKed data byte
+        let mut i2c = master();
+        i2c.sda.port().accept = 1;
+        let err = i2c.write(ADDR, &[1, 2, 3]).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::I2c(I2cErrorKind::NoAcknowledge(NoAcknowledgeSource::Data))));
+        assert_eq!(i2c.sda.port().log, [
+            Event::Start,
+            Event::Write(ADDR << 1, true),
+            Event::Write(1, true),
+            Event::Write(2, false),
+            Event::Stop,
+        ]);
+    }
+
+    #[test]
+    fn test_zero_length() {
+        let mut i2c = master();
+        i2c.transaction(ADDR, &mut []).unwrap();
+        assert!(i2c.sda.port().log.is_empty());
+
+        i2c.read(ADDR, &mut []).unwrap();
+        i2c.write(ADDR, &[]).unwrap();
+        assert_eq!(i2c.sda.port().log, [
+            Event::Start,
+            Event::Write(ADDR << 1 | 1, true),
+            Event::Read(0x10, false),
+            Event::Stop,
+            Event::Start,
+            Event::Write(ADDR << 1, true),
+            Event::Stop,
+        ]);
+    }
+
+    #[test]
+    fn test_clock_stretching() {
+        let mut i2c = master();
+        i2c.sda.port().stretch = 3;
+        let mut buf = [0u8; 1];
+        i2c.write_read(ADDR, &[0xC0], &mut buf).unwrap();
+        assert_eq!(buf, [0x10]);
+        assert_eq!(i2c.sda.port().log.last(), Some(&Event::Stop));
+
+        let mut i2c = master().with_stretch_timeout(Duration::from_millis(1));
+        i2c.sda.port().stretch = usize::MAX;
+        let err = i2c.write(ADDR, &[1]).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::BusStuck { line: Signal::I2cScl }));
+        assert_eq!(embedded_hal::i2c::Error::kind(&err), I2cErrorKind::Bus);
+    }
+
+    #[test]
+    fn test_arbitration_loss() {
+        let mut i2c = master();
+        // 0xA0 wins against 0xB4 at bit 4, the loser must not send a STOP
+        i2c.sda.port().contender = Some(0xA0);
+        let err = i2c.write(ADDR, &[1]).unwrap_err();
+        assert!(err.is_transient());
+        assert!(matches!(err.kind(), ErrorKind::I2c(I2cErrorKind::ArbitrationLoss)));
+        assert_eq!(i2c.sda.port().log, [Event::Start]);
+        let bus = i2c.sda.port();
+        assert!(bus.master_scl && bus.master_sda);
+    }
+
+    #[test]
+    fn test_bus_recovery() {
+        // Slave interrupted in the middle of sending 0x00
+        let mut i2c = master();
+        i2c.sda.port().phase = Phase::Transmit;
+        i2c.sda.port().shift = 0x00;
+        i2c.sda.port().bits = 3;
+        i2c.sda.port().slave_sda = false;
+
+        i2c.write(ADDR, &[1]).unwrap();
+        assert_eq!(i2c.sda.port().log, [
+            Event::Read(0x00, false),
+            Event::Stop,
+            Event::Start,
+            Event::Write(ADDR << 1, true),
+            Event::Write(1, true),
+            Event::Stop,
+        ]);
+
+        let mut i2c = master();
+        i2c.sda.port().sda_stuck = true;
+        let err = i2c.recover_bus().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::BusStuck { line: Signal::I2cSda }));
+        assert!(i2c.write(ADDR, &[1]).is_err());
+    }
+
+    #[test]
+    fn test_repeated_start_sda_low() {
+        // The slave keeps SDA low after the last write byte, recovering would send a STOP
+        let mut i2c = master();
+        i2c.sda.port().hold_after = Some(3);
+        let mut buf = [0u8; 1];
+        let err = i2c.write_read(ADDR, &[0xC1, 0x0C], &mut buf).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::I2c(I2cErrorKind::ArbitrationLoss)));
+        assert_eq!(i2c.sda.port().log, [
+            Event::Start,
+            Event::Write(ADDR << 1, true),
+            Event::Write(0xC1, true),
+            Event::Write(0x0C, true),
+        ]);
+    }
+
+    #[test]
+    fn test_port_pins_are_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Master>();
+    }
+
+    /// Levels of two plain pins wired to nothing but the pull-ups
+    #[derive(Debug, Default)]
+    struct Wires {
+        /// (SCL, SDA) after every pin change
+        levels: Vec<(bool, bool)>,
+        scl: bool,
+        sda: bool,
+        /// A slave holding SCL low for good
+        scl_held: bool,
+        scl_reads: usize,
+    }
+
+    /// Open-drain pin on [`Wires`], independent of any [`GpioPort`]
+    struct WirePin {
+        wires: Rc<RefCell<Wires>>,
+        scl: bool,
+    }
+
+    impl PinErrorType for WirePin {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for WirePin {
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.set(false);
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.set(true);
+            Ok(())
+        }
+    }
+
+    impl InputPin for WirePin {
+        fn is_high(&mut self) -> Result<bool, Infallible> {
+            let mut wires = self.wires.borrow_mut();
+            if self.scl {
+                wires.scl_reads += 1;
+                Ok(wires.scl && !wires.scl_held)
+            } else {
+                Ok(wires.sda)
+            }
+        }
+
+        fn is_low(&mut self) -> Result<bool, Infallible> {
+            Ok(!self.is_high()?)
+        }
+    }
+
+    impl WirePin {
+        fn pair() -> (Rc<RefCell<Wires>>, WirePin, WirePin) {
+            let wires = Rc::new(RefCell::new(Wires {
+                scl: true,
+                sda: true,
+                ..Default::default()
+            }));
+            let sda = WirePin { wires: wires.clone(), scl: false };
+            let scl = WirePin { wires: wires.clone(), scl: true };
+            (wires, sda, scl)
+        }
+
+        fn set(&mut self, level: bool) {
+            let mut wires = self.wires.borrow_mut();
+            if self.scl {
+                wires.scl = level;
+            } else {
+                wires.sda = level;
+            }
+            let levels = (wires.scl, wires.sda);
+            wires.levels.push(levels);
+        }
+    }
+
+    #[test]
+    fn test_plain_pins() {
+        let (wires, sda, scl) = WirePin::pair();
+        let mut i2c = BitbangI2c::with_pins(sda, scl, NoDelay).with_clock(NoClock);
+
+        // Nothing pulls SDA low for the ACK
+        let err = i2c.write(ADDR, &[1]).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::I2c(I2cErrorKind::NoAcknowledge(NoAcknowledgeSource::Address))));
+
+        let levels = &wires.borrow().levels;
+        let start = levels.iter().position(|&level| level == (true, false)).unwrap();
+        assert_eq!(levels[start - 1], (true, true));
+        assert_eq!(levels[start + 1], (false, false));
+        // STOP, both lines released at the end
+        assert_eq!(levels[levels.len() - 2..], [(true, false), (true, true)]);
+    }
+
+    /// Advances 100ms on every reading
+    struct StepClock(Cell<Duration>);
+
+    impl PollClock for StepClock {
+        fn now(&mut self) -> Duration {
+            let now = self.0.get() + Duration::from_millis(100);
+            self.0.set(now);
+            now
+        }
+    }
+
+    #[test]
+    fn test_stretch_timeout_clock() {
+        let (wires, sda, scl) = WirePin::pair();
+        wires.borrow_mut().scl_held = true;
+        let mut i2c = BitbangI2c::with_pins(sda, scl, NoDelay)
+            .with_clock(StepClock(Cell::new(Duration::ZERO)))
+            .with_timing(I2cTiming {
+                stretch_timeout: Duration::from_secs(2),
+                stretch_poll_ns: 100_000,
+                ..I2cTiming::STANDARD_MODE
+            });
+
+        let err = i2c.write(ADDR, &[1]).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::BusStuck { line: Signal::I2cScl }));
+        // Only the START waits, summed poll delays alone would take 20000 reads
+        assert!(wires.borrow().scl_reads <= 25);
+        // No STOP, both lines are just released
+        assert_eq!(wires.borrow().levels.last(), Some(&(true, true)));
+    }
+}
@@ -0,0 +1,512 @@
+//! MPSSE I2C master for FTDI devices
+//!
+//! The MPSSE engine clocks whole bytes, so a byte is queued as one command
+//! stream instead of several USB transfers per bit like
+//! [`I2cFtBitbang`](super::i2c_bitbang::I2cFtBitbang). Reads are sent in a
+//! single transfer. The engine cannot stop on a NACK by itself, so every
+//! written byte waits for its ACK in a transfer of its own.
+//!
+//! Only channels A and B of the FT4232H have an MPSSE, channels C and D
+//! support bitbang only. On the FACET2 the I2C bus is wired to channel C, so
+//! this master needs other hardware, e.g. a FT232H or FT2232H breakout.
+//!
+//! Wiring, as in FTDI AN_255 plus the clock sense input:
+//!
+//! | Pin | Function                           |
+//! |-----|------------------------------------|
+//! | AD0 | SCL                                |
+//! | AD1 | SDA out                            |
+//! | AD2 | SDA in, connected to AD1           |
+//! | AD7 | SCL sense (RTCK), connected to SCL |
+//!
+//! Clocking is adaptive: after each SCL edge the MPSSE waits until AD7 reads
+//! the same level, so a slave stretching the clock (the STM32 bootloader does
+//! during flash operations) holds the transfer instead of being overrun.
+//! Without AD7 wired the engine stalls on the first byte.
+//!
+//! Open-drain is emulated for START, STOP, ACK and idle by switching the
+//! pins between output low and input. On the FT232H, AD0 and AD1 are also
+//! put into drive-zero mode, so clocked bits never drive the bus high. The
+//! FT2232H and FT4232H lack that mode and clock SCL and data push-pull; put
+//! a Schottky diode between AD0 and SCL and one between AD1 and SDA, cathodes
+//! at the FTDI pins, and connect AD2 and AD7 on the bus side, so the channel
+//! can only pull the lines low.
+
+use crate::prelude::*;
+
+use embedded_hal::i2c::{ErrorKind as I2cErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation};
+use libftd2xx::{BitMode, DeviceType, FtdiCommon, FtdiMpsse, MpsseCmdExecutor};
+
+use crate::error::{Error, ErrorKind};
+
+const SCL: u8 = 1 << 0; // AD0
+const SDA_OUT: u8 = 1 << 1; // AD1
+// AD2 (SDA in) stays an input
+
+/// Standard-mode bus speed
+pub const STANDARD_MODE_HZ: u32 = 100_000;
+/// Fast-mode bus speed
+pub const FAST_MODE_HZ: u32 = 400_000;
+/// Slowest bus speed, limited by the 16-bit clock divisor
+pub const MIN_BUS_HZ: u32 = 306;
+/// Fastest bus speed, Fast-mode Plus
+pub const MAX_BUS_HZ: u32 = 1_000_000;
+
+/// 60 MHz base clock, halved by the divider and stretched by 3-phase clocking
+const THREE_PHASE_BASE_HZ: u32 = 20_000_000;
+
+/// GPIO commands are repeated to stretch START/STOP setup and hold times
+const HOLD_REPEATS: usize = 4;
+
+/// Responses queued before the commands are flushed, below the 2 KiB receive buffer
+const MAX_PENDING_RESPONSES: usize = 1024;
+
+/// MPSSE opcodes
+const MPSSE_SET_GPIO_LOWER: u8 = 0x80;
+const MPSSE_BYTES_OUT_MSB_NEG: u8 = 0x11;
+const MPSSE_BITS_OUT_MSB_NEG: u8 = 0x13;
+const MPSSE_BYTES_IN_MSB_POS: u8 = 0x20;
+const MPSSE_BITS_IN_MSB_POS: u8 = 0x22;
+const MPSSE_LOOPBACK_OFF: u8 = 0x85;
+const MPSSE_SET_DIVISOR: u8 = 0x86;
+const MPSSE_SEND_IMMEDIATE: u8 = 0x87;
+const MPSSE_CLK_DIV5_OFF: u8 = 0x8A;
+const MPSSE_3PHASE_ON: u8 = 0x8C;
+const MPSSE_ADAPTIVE_ON: u8 = 0x96;
+const MPSSE_DRIVE_ZERO: u8 = 0x9E;
+
+/// Clock divisor for `bus_hz`, rounded so the bus is never faster than requested
+fn clock_divisor(bus_hz: u32) -> Result<u16, Error> {
+    if !(MIN_BUS_HZ..=MAX_BUS_HZ).contains(&bus_hz) {
+        return Err(ErrorKind::UnsupportedClock { clock_hz: bus_hz }.into());
+    }
+    Ok((THREE_PHASE_BASE_HZ.div_ceil(bus_hz) - 1) as u16)
+}
+
+/// Byte the device sends back for a queued command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Response {
+    /// ACK bit sampled after sending a byte, in bit 0
+    Ack(NoAcknowledgeSource),
+    Data,
+}
+
+/// Command stream of one transaction
+#[derive(Debug, Default)]
+struct Batch {
+    commands: Vec<u8>,
+    responses: Vec<Response>,
+    received: Vec<u8>,
+}
+
+impl Batch {
+    /// Set the lines, `true` releases a line to the pull-up
+    fn lines(&mut self, scl: bool, sda: bool) {
+        let mut direction = 0;
+        if !scl {
+            direction |= SCL;
+        }
+        if !sda {
+            direction |= SDA_OUT;
+        }
+        self.commands.extend_from_slice(&[MPSSE_SET_GPIO_LOWER, 0, direction]);
+    }
+
+    fn hold(&mut self, scl: bool, sda: bool) {
+        for _ in 0..HOLD_REPEATS {
+            self.lines(scl, sda);
+        }
+    }
+
+    /// START, or repeated START after a byte
+    fn start(&mut self) {
+        self.hold(true, true);
+        self.hold(true, false);
+        self.hold(false, false);
+    }
+
+    fn stop(&mut self) {
+        self.hold(false, false);
+        self.hold(true, false);
+        self.hold(true, true);
+    }
+
+    fn write_byte(&mut self, byte: u8, source: NoAcknowledgeSource) {
+        // Drive SDA for the data bits, release it for the ACK
+        self.lines(false, false);
+        self.commands.extend_from_slice(&[MPSSE_BYTES_OUT_MSB_NEG, 0, 0, byte]);
+        self.lines(false, true);
+        self.commands.extend_from_slice(&[MPSSE_BITS_IN_MSB_POS, 0]);
+        self.responses.push(Response::Ack(source));
+    }
+
+    fn read_byte(&mut self, nack: bool) {
+        self.lines(false, true);
+        self.commands.extend_from_slice(&[MPSSE_BYTES_IN_MSB_POS, 0, 0]);
+        self.responses.push(Response::Data);
+        // A NACK is a released SDA, an ACK pulls it low
+        self.lines(false, nack);
+        self.commands.extend_from_slice(&[MPSSE_BITS_OUT_MSB_NEG, 0, 0]);
+        self.lines(false, true);
+    }
+
+    fn pending(&self) -> usize {
+        self.responses.len() - self.received.len()
+    }
+
+    /// First NACK among the received responses
+    fn check_acks(&self) -> Result<(), Error> {
+        let nack = self
+            .responses
+            .iter()
+            .zip(&self.received)
+            .find_map(|(response, byte)| match response {
+                Response::Ack(source) if byte & 1 != 0 => Some(*source),
+                _ => None,
+            });
+        match nack {
+            Some(source) => Err(I2cErrorKind::NoAcknowledge(source).into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Received data bytes in order
+    fn data(&self) -> impl Iterator<Item = u8> + '_ {
+        self.responses
+            .iter()
+            .zip(&self.received)
+            .filter(|(response, _)| **response == Response::Data)
+            .map(|(_, byte)| *byte)
+    }
+}
+
+/// I2C master using the MPSSE engine of an FTDI channel
+pub struct I2cFtMpsse<D> {
+    device: D,
+    bus_hz: u32,
+}
+
+impl<D> I2cFtMpsse<D>
+where
+    D: FtdiMpsse + MpsseCmdExecutor,
+    Error: From<<D as MpsseCmdExecutor>::Error>,
+{
+    /// Put the channel into MPSSE mode and release the bus
+    ///
+    /// Enables adaptive clocking, and drive-zero mode on SCL and SDA if the
+    /// device is a FT232H, see the module docs for the wiring.
+    ///
+    /// Fails with [`ErrorKind::UnsupportedClock`] if `bus_hz` is outside
+    /// [`MIN_BUS_HZ`] to [`MAX_BUS_HZ`].
+    pub fn new(mut device: D, bus_hz: u32) -> Result<Self, Error> {
+        let divisor = clock_divisor(bus_hz)?;
+        let open_drain = device.device_type()? == DeviceType::FT232H;
+
+        device.set_bit_mode(0, BitMode::Reset)?;
+        device.set_bit_mode(0, BitMode::Mpsse)?;
+        device.set_latency_timer(Duration::from_millis(2))?;
+
+        let mut batch = Batch::default();
+        if open_drain {
+            batch.commands.extend_from_slice(&[MPSSE_DRIVE_ZERO, SCL | SDA_OUT, 0]);
+        }
+        batch.commands.extend_from_slice(&[
+            MPSSE_LOOPBACK_OFF,
+            MPSSE_CLK_DIV5_OFF,
+            MPSSE_ADAPTIVE_ON,
+            MPSSE_3PHASE_ON,
+            MPSSE_SET_DIVISOR,
+        ]);
+        batch.commands.extend_from_slice(&divisor.to_le_bytes());
+        batch.lines(true, true);
+        device.send(&batch.commands)?;
+
+        Ok(Self { device, bus_hz })
+    }
+}
+
+impl<D> I2cFtMpsse<D>
+where
+    D: MpsseCmdExecutor,
+    Error: From<D::Error>,
+{
+    pub fn bus_speed(&self) -> u32 {
+        self.bus_hz
+    }
+
+    /// Change the SCL frequency
+    pub fn set_bus_speed(&mut self, bus_hz: u32) -> Result<(), Error> {
+        let divisor = clock_divisor(bus_hz)?.to_le_bytes();
+        self.device.send(&[MPSSE_SET_DIVISOR, divisor[0], divisor[1]])?;
+        self.bus_hz = bus_hz;
+        Ok(())
+    }
+
+    /// Send the queued commands and collect the responses
+    fn flush(&mut self, batch: &mut Batch) -> Result<(), Error> {
+        let pending = batch.pending();
+        if pending > 0 {
+            batch.commands.push(MPSSE_SEND_IMMEDIATE);
+        }
+        if !batch.commands.is_empty() {
+            self.device.send(&batch.commands)?;
+            batch.commands.clear();
+        }
+        if pending > 0 {
+            let start = batch.received.len();
+            batch.received.resize(start + pending, 0);
+            self.device.recv(&mut batch.received[start..])?;
+        }
+        batch.check_acks()
+    }
+
+    /// Queue everything between the first START and the STOP
+    fn queue_transaction(&mut self, batch: &mut Batch, address: u8, operations: &[Operation<'_>]) -> Result<(), Error> {
+        for group in operations.chunk_by(|a, b| matches!(a, Operation::Read(_)) == matches!(b, Operation::Read(_))) {
+            let read = matches!(group[0], Operation::Read(_));
+            batch.start();
+            batch.write_byte(address << 1 | u8::from(read), NoAcknowledgeSource::Address);
+            // Nothing may follow a NACK, so each written byte is a round trip
+            self.flush(batch)?;
+
+            if read {
+                let total: usize = group
+                    .iter()
+                    .map(|op| match op {
+                        Operation::Read(rd) => rd.len(),
+                        Operation::Write(_) => 0,
+                    })
+                    .sum();
+                // Empty reads still take one byte to free SDA, see `I2cFtBitbang`
+                for i in 0..total.max(1) {
+                    batch.read_byte(i + 1 >= total);
+                    if batch.pending() >= MAX_PENDING_RESPONSES {
+                        self.flush(batch)?;
+                    }
+                }
+            } else {
+                for op in group {
+                    if let Operation::Write(wr) = op {
+                        for &byte in wr.iter() {
+                            batch.write_byte(byte, NoAcknowledgeSource::Data);
+                            self.flush(batch)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<D> I2c for I2cFtMpsse<D>
+where
+    D: MpsseCmdExecutor,
+    Error: From<D::Error>,
+{
+    /// Send every written byte on its own and queue the reads
+    ///
+    /// A NACK ends the transaction before the next byte, followed by a STOP.
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        if operations.is_empty() {
+            return Ok(());
+        }
+
+        let mut batch = Batch::default();
+        let result = self.queue_transaction(&mut batch, address, operations);
+        batch.stop();
+        let flushed = self.flush(&mut batch);
+        result.and(flushed)?;
+
+        let mut data = batch.data();
+        for op in operations {
+            if let Operation::Read(rd) = op {
+                for byte in rd.iter_mut() {
+                    *byte = data.next().unwrap_or(0xFF);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<D> ErrorType for I2cFtMpsse<D> {
+    type Error = Error;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::VecDeque;
+
+    use libftd2xx::FtStatus;
+
+    /// Records the command stream and answers reads from a script, zeros after it
+    #[derive(Default)]
+    struct MockMpsse {
+        sent: Vec<u8>,
+        replies: VecDeque<u8>,
+        recv_lengths: Vec<usize>,
+    }
+
+    impl MpsseCmdExecutor for MockMpsse {
+        type Error = FtStatus;
+
+        fn send(&mut self, data: &[u8]) -> Result<(), FtStatus> {
+            self.sent.extend_from_slice(data);
+            Ok(())
+        }
+
+        fn recv(&mut self, data: &mut [u8]) -> Result<(), FtStatus> {
+            for byte in data.iter_mut() {
+                *byte = self.replies.pop_front().unwrap_or(0);
+            }
+            self.recv_lengths.push(data.len());
+            Ok(())
+        }
+    }
+
+    fn mock(replies: &[u8]) -> I2cFtMpsse<MockMpsse> {
+        I2cFtMpsse {
+            device: MockMpsse {
+                replies: replies.iter().copied().collect(),
+                ..Default::default()
+            },
+            bus_hz: STANDARD_MODE_HZ,
+        }
+    }
+
+    /// Number of times `byte` is clocked out
+    fn count_writes(sent: &[u8], byte: u8) -> usize {
+        sent.windows(4)
+            .filter(|cmd| *cmd == [MPSSE_BYTES_OUT_MSB_NEG, 0, 0, byte])
+            .count()
+    }
+
+    #[test]
+    fn test_clock_divisor() {
+        assert_eq!(clock_divisor(STANDARD_MODE_HZ).unwrap(), 199);
+        assert_eq!(clock_divisor(FAST_MODE_HZ).unwrap(), 49);
+        assert_eq!(clock_divisor(MAX_BUS_HZ).unwrap(), 19);
+        assert_eq!(clock_divisor(MIN_BUS_HZ).unwrap(), 65359);
+        assert!(matches!(
+            clock_divisor(MAX_BUS_HZ + 1).map_err(Error::into_kind),
+            Err(ErrorKind::UnsupportedClock { clock_hz: 1_000_001 })
+        ));
+    }
+
+    #[test]
+    fn test_write_byte_commands() {
+        let mut batch = Batch::default();
+        batch.write_byte(0xB4, NoAcknowledgeSource::Address);
+        assert_eq!(batch.commands, [
+            0x80, 0x00, 0x03,
+            0x11, 0x00, 0x00, 0xB4,
+            0x80, 0x00, 0x01,
+            0x22, 0x00,
+        ]);
+
+        let mut batch = Batch::default();
+        batch.read_byte(true);
+        assert_eq!(batch.commands, [
+            0x80, 0x00, 0x01,
+            0x20, 0x00, 0x00,
+            0x80, 0x00, 0x01,
+            0x13, 0x00, 0x00,
+            0x80, 0x00, 0x01,
+        ]);
+        assert_eq!(batch.responses, [Response::Data]);
+    }
+
+    #[test]
+    fn test_start_stop_levels() {
+        let mut batch = Batch::default();
+        batch.start();
+        batch.stop();
+        let directions: Vec<u8> = batch.commands.chunks(3).map(|cmd| cmd[2]).collect();
+        let mut expected = Vec::new();
+        for direction in [0, SDA_OUT, SCL | SDA_OUT, SCL | SDA_OUT, SDA_OUT, 0] {
+            expected.extend([direction; HOLD_REPEATS]);
+        }
+        assert_eq!(directions, expected);
+    }
+
+    #[test]
+    fn test_responses() {
+        let mut batch = Batch::default();
+        batch.write_byte(0xB5, NoAcknowledgeSource::Address);
+        batch.read_byte(false);
+        batch.read_byte(true);
+        batch.write_byte(0x01, NoAcknowledgeSource::Data);
+        assert_eq!(batch.pending(), 4);
+
+        batch.received = vec![0xFE, 0x12, 0x34, 0x00];
+        assert!(batch.check_acks().is_ok());
+        assert_eq!(batch.data().collect::<Vec<_>>(), [0x12, 0x34]);
+
+        batch.received[3] = 0x01;
+        assert!(matches!(
+            batch.check_acks().unwrap_err().kind(),
+            ErrorKind::I2c(I2cErrorKind::NoAcknowledge(NoAcknowledgeSource::Data))
+        ));
+        batch.received[0] = 0xFF;
+        assert!(matches!(
+            batch.check_acks().unwrap_err().kind(),
+            ErrorKind::I2c(I2cErrorKind::NoAcknowledge(NoAcknowledgeSource::Address))
+        ));
+    }
+
+    #[test]
+    fn test_transaction_groups() {
+        // Two ACKed address bytes and writes, then the read data
+        let mut i2c = mock(&[0, 0, 0, 0, 0x12, 0x34, 0x56]);
+        let mut first = [0; 2];
+        let mut second = [0; 1];
+        i2c.transaction(0x5A, &mut [
+            Operation::Write(&[0x01]),
+            Operation::Write(&[0x02]),
+            Operation::Read(&mut first),
+            Operation::Read(&mut second),
+        ])
+        .unwrap();
+
+        assert_eq!(first, [0x12, 0x34]);
+        assert_eq!(second, [0x56]);
+        // Adjacent operations of the same kind share one START and address
+        assert_eq!(count_writes(&i2c.device.sent, 0x5A << 1), 1);
+        assert_eq!(count_writes(&i2c.device.sent, 0x5A << 1 | 1), 1);
+        // Written bytes wait for their ACK one by one, the reads go out together
+        assert_eq!(i2c.device.recv_lengths, [1, 1, 1, 1, 3]);
+        assert_eq!(i2c.device.sent.iter().filter(|&&b| b == MPSSE_SEND_IMMEDIATE).count(), 5);
+    }
+
+    #[test]
+    fn test_transaction_nack() {
+        let mut i2c = mock(&[0, 0, 1, 0]);
+        let result = i2c.transaction(0x5A, &mut [Operation::Write(&[0x01, 0x02, 0x03])]);
+        assert!(matches!(
+            result.map_err(Error::into_kind),
+            Err(ErrorKind::I2c(I2cErrorKind::NoAcknowledge(NoAcknowledgeSource::Data)))
+        ));
+        // Nothing is clocked after the NACK, the bus is still released with a STOP
+        let sent = &i2c.device.sent;
+        assert_eq!(count_writes(sent, 0x03), 0);
+        assert_eq!(i2c.device.recv_lengths, [1, 1, 1]);
+        assert_eq!(sent[sent.len() - 3..], [MPSSE_SET_GPIO_LOWER, 0, 0]);
+    }
+
+    #[test]
+    fn test_long_read_flushes() {
+        let mut i2c = mock(&[]);
+        let mut buf = vec![0xAA; MAX_PENDING_RESPONSES * 2];
+        i2c.read(0x5A, &mut buf).unwrap();
+        assert!(buf.iter().all(|&byte| byte == 0));
+        assert!(i2c.device.recv_lengths.iter().all(|&len| len <= MAX_PENDING_RESPONSES));
+        assert_eq!(i2c.device.recv_lengths.iter().sum::<usize>(), buf.len() + 1);
+    }
+}
@@ -1,10 +1,26 @@
-use embedded_hal::i2c::I2c;
+use embedded_hal::i2c::{Error as _, ErrorKind as I2cErrorKind, I2c};
+use embedded_io::SeekFrom;
 use crate::prelude::*;
+use crate::error::{Error, ErrorKind, SourceError};
+use crate::DelayTrait;
 
 pub const FLASH_SIZE: usize = 0x24400; // 145KB
 pub const READ_CHUNK_SIZE: usize = 64;
 const STATUS_PREFIX_SZ: usize = 2;
 
+/// Number of reads of a chunk in [`VerifyMode::DoubleRead`] before giving up
+const VERIFY_READS: usize = 4;
+
+/// How flash chunks are checked before they are returned
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VerifyMode {
+    /// Trust a single read
+    #[default]
+    None,
+    /// Read every chunk until two consecutive reads agree
+    DoubleRead,
+}
+
 #[allow(non_camel_case_types)]
 #[repr(u8)]
 #[derive(Debug)]
@@ -27,7 +43,7 @@ impl From<Isd9160Commands> for u8 {
 
 #[allow(non_camel_case_types)]
 #[repr(u8)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Isd9160Registers {
     /// R/W I2C Control Register
     REG_CTL = 0x00,
@@ -86,152 +102,559 @@ impl From<Isd9160Sounds> for u8 {
     }
 }
 
-pub struct Isd9160<T>
+/// Default number of times a NACKed transfer is repeated
+pub const DEFAULT_NACK_RETRIES: u8 = 3;
+
+/// Default wait before repeating a NACKed transfer
+pub const DEFAULT_NACK_RETRY_INTERVAL: Duration = Duration::from_millis(1);
+
+pub struct Isd9160<T, D>
 {
     device: T,
+    delay: D,
     position: u64,
-    write_reg_buf: [u8; 6],
+    nack_retries: u8,
+    nack_retry_interval: Duration,
+    verify: VerifyMode,
 }
 
-impl<T> Isd9160<T>
+impl<T, D> Isd9160<T, D>
 where
-    T: I2c
+    T: I2c<Error: SourceError>,
+    D: DelayTrait,
 {
     /// Nuvoton ISD9160 Soundcorder Chip (RF Unit)
     pub const I2C_ADDR: u8 = 0x5A;
 
-    pub fn new(device: T) -> Self {
+    pub fn new(device: T, delay: D) -> Self {
         Self {
             device,
+            delay,
             position: 0,
-            write_reg_buf: [0u8; _],
+            nack_retries: DEFAULT_NACK_RETRIES,
+            nack_retry_interval: DEFAULT_NACK_RETRY_INTERVAL,
+            verify: VerifyMode::None,
         }
     }
 
+    /// Verify flash reads, see [`VerifyMode`]
+    pub fn with_verify(mut self, verify: VerifyMode) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Repeat NACKed transfers up to `retries` times, `interval` apart, before failing
+    ///
+    /// The chip does not acknowledge while it is busy, e.g. right after a
+    /// reset. Other bus errors are never retried.
+    pub fn with_nack_retries(mut self, retries: u8, interval: Duration) -> Self {
+        self.nack_retries = retries;
+        self.nack_retry_interval = interval;
+        self
+    }
+
     pub fn flash_size(&self) -> usize { FLASH_SIZE }
 
-    pub fn read_interrupt(&mut self) -> u16 {
+    #[cfg(test)]
+    pub(crate) fn device(&self) -> &T {
+        &self.device
+    }
+
+    /// Run a transfer, repeating it while the chip NACKs
+    fn transfer<R>(&mut self, mut op: impl FnMut(&mut T) -> Result<R, T::Error>) -> Result<R, Error> {
+        let mut retries = self.nack_retries;
+        loop {
+            match op(&mut self.device) {
+                Ok(value) => return Ok(value),
+                Err(e) if retries > 0 && matches!(e.kind(), I2cErrorKind::NoAcknowledge(_)) => {
+                    retries -= 1;
+                    self.delay.delay_us(
+                        self.nack_retry_interval.as_micros().try_into().unwrap_or(u32::MAX),
+                    );
+                }
+                Err(e) => return Err(Error::i2c(e)),
+            }
+        }
+    }
+
+    /// Send a command without response
+    pub(crate) fn write_command(&mut self, cmd: &[u8]) -> Result<(), Error> {
+        self.transfer(|device| device.write(Self::I2C_ADDR, cmd))
+    }
+
+    /// Read the raw interrupt word
+    ///
+    /// TODO: the bits are not decoded - capture the word while the console
+    /// plays its boot sound and while it rejects a sound index.
+    pub fn read_interrupt(&mut self) -> Result<u16, Error> {
         let cmd: [u8; 1] = [Isd9160Commands::CMD_INTERRUPT_READ.into()];
         let mut read = [0u8; 2];
-        self.device
-            .write_read(Self::I2C_ADDR, &cmd, &mut read)
-            .expect("Failed to read register");
+        self.transfer(|device| device.write_read(Self::I2C_ADDR, &cmd, &mut read))?;
 
-        u16::from_le_bytes(read)
+        Ok(u16::from_le_bytes(read))
     }
 
-    pub fn read_register<U: Into<u8>>(&mut self, register: U) -> u32 {
-        let cmd = [Isd9160Commands::CMD_REG_READ.into(), register.into()];
+    pub fn read_register<U: Into<u8>>(&mut self, register: U) -> Result<u32, Error> {
+        let register = register.into();
+        let cmd = [Isd9160Commands::CMD_REG_READ.into(), register];
         let mut read = [0u8; 4];
-        self.device
-            .write_read(Self::I2C_ADDR, &cmd, &mut read)
-            .expect("Failed to read register");
+        self.transfer(|device| device.write_read(Self::I2C_ADDR, &cmd, &mut read))
+            .map_err(|e| e.with_register(register))?;
 
-        u32::from_le_bytes(read)
+        Ok(u32::from_le_bytes(read))
     }
 
-    pub fn write_register<U: Into<u8>>(&mut self, register: U, data: &[u8]) {
-        self.write_reg_buf[0] = Isd9160Commands::CMD_REG_WRITE.into();
-        self.write_reg_buf[1] = register.into();
-        self.write_reg_buf[2..].copy_from_slice(data);
-        self.device
-            .write(Self::I2C_ADDR, &self.write_reg_buf)
-            .expect("Failed to write register");
+    /// Write up to 4 bytes, little-endian; shorter values are zero-padded
+    pub fn write_register<U: Into<u8>>(&mut self, register: U, data: &[u8]) -> Result<(), Error> {
+        let register = register.into();
+        if data.len() > 4 {
+            return Err(Error::from(ErrorKind::OutOfBounds).with_register(register));
+        }
+
+        let mut buf = [0u8; 6];
+        buf[0] = Isd9160Commands::CMD_REG_WRITE.into();
+        buf[1] = register;
+        buf[2..2 + data.len()].copy_from_slice(data);
+        self.transfer(|device| device.write(Self::I2C_ADDR, &buf))
+            .map_err(|e| e.with_register(register))
     }
 
-    pub fn init(&mut self) {
-        self.write_register(Isd9160Registers::REG_STATUS, &[0x01]);
-        self.write_register(Isd9160Registers::REG_ADDR0, &[0xFF, 0xFF]);
+    pub fn init(&mut self) -> Result<(), Error> {
+        self.write_register(Isd9160Registers::REG_STATUS, &[0x01])?;
+        self.write_register(Isd9160Registers::REG_ADDR0, &[0xFF, 0xFF])
     }
 
-    pub fn reset(&mut self) {
-        self.device
-            .write(Self::I2C_ADDR, &[Isd9160Commands::CMD_RESET.into(), 0x55])
-            .expect("Failed to reset");
+    pub fn reset(&mut self) -> Result<(), Error> {
+        self.write_command(&[Isd9160Commands::CMD_RESET.into(), 0x55])
     }
 
-    pub fn play_sound<U: Into<u8>>(&mut self, sound_index: U) {
-        self.device
-            .write(
-                Self::I2C_ADDR,
-                &[Isd9160Commands::CMD_START.into(), sound_index.into()],
-            )
-            .expect("Failed to play sound");
+    pub fn play_sound<U: Into<u8>>(&mut self, sound_index: U) -> Result<(), Error> {
+        self.write_command(&[Isd9160Commands::CMD_START.into(), sound_index.into()])
     }
 
-    pub fn stop(&mut self) {
-        self.device
-            .write(Self::I2C_ADDR, &[Isd9160Commands::CMD_STOP.into()])
-            .expect("Failed to stop");
+    pub fn stop(&mut self) -> Result<(), Error> {
+        self.write_command(&[Isd9160Commands::CMD_STOP.into()])
     }
 
-    /// This reads 6 bytes at a time
-    fn read_data(&mut self, addr: u32) -> [u8; 6] {
+    /// Read one 64-byte chunk together with the raw status word sent before it
+    ///
+    /// The status bits were never captured, so the word is returned as is
+    /// for auditing dumps. `addr` has to be aligned to [`READ_CHUNK_SIZE`].
+    pub fn read_chunk_with_status(&mut self, addr: u32) -> Result<(u16, [u8; READ_CHUNK_SIZE]), Error> {
+        if !(addr as usize).is_multiple_of(READ_CHUNK_SIZE) {
+            return Err(ErrorKind::NotAligned.into());
+        }
+        if addr as usize >= FLASH_SIZE {
+            return Err(ErrorKind::OutOfBounds.into());
+        }
+
         let mut buf = [0u8; READ_CHUNK_SIZE + STATUS_PREFIX_SZ];
 
         let mut cmd: [u8; 5] = [Isd9160Commands::CMD_FLASH_READ.into(), 0, 0, 0, 0];
         let addr_bytes = addr.to_le_bytes();
         cmd[1..].copy_from_slice(&addr_bytes);
 
-        self.device
-            .write_read(Self::I2C_ADDR, &cmd, &mut buf)
-            .expect("Failed to read data");
+        self.transfer(|device| device.write_read(Self::I2C_ADDR, &cmd, &mut buf))?;
+
+        let status = u16::from_le_bytes([buf[0], buf[1]]);
+        let mut chunk = [0u8; READ_CHUNK_SIZE];
+        chunk.copy_from_slice(&buf[STATUS_PREFIX_SZ..]);
+        Ok((status, chunk))
+    }
+
+    /// Read one 64-byte chunk, checking that the status word was driven
+    ///
+    /// All ones means nobody drove SDA, all zeros a line held low. Either
+    /// fails with [`ErrorKind::InvalidReadStatus`], other values are accepted.
+    fn read_data(&mut self, addr: u32) -> Result<[u8; READ_CHUNK_SIZE], Error> {
+        let (status, chunk) = self.read_chunk_with_status(addr)?;
+        if status == 0x0000 || status == 0xFFFF {
+            return Err(ErrorKind::InvalidReadStatus { status }.into());
+        }
+        Ok(chunk)
+    }
+
+    /// Read one chunk according to the [`VerifyMode`]
+    fn read_chunk(&mut self, addr: u32) -> Result<[u8; READ_CHUNK_SIZE], Error> {
+        let mut chunk = self.read_data(addr)?;
+        if self.verify == VerifyMode::None {
+            return Ok(chunk);
+        }
+
+        for _ in 1..VERIFY_READS {
+            let again = self.read_data(addr)?;
+            if again == chunk {
+                return Ok(chunk);
+            }
+            chunk = again;
+        }
+        Err(ErrorKind::VerifyMismatch { address: addr }.into())
+    }
+
+    /// Move the flash read position, shared by the `embedded-io` and `std::io` impls
+    fn seek_flash(&mut self, pos: SeekFrom) -> Result<u64, Error> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => (0, offset as i64),
+            SeekFrom::End(offset) => (FLASH_SIZE as i64, offset),
+            SeekFrom::Current(offset) => (self.position as i64, offset),
+        };
+
+        let new_pos = base.checked_add(offset).ok_or(ErrorKind::SeekOutOfRange)?;
+        if !(0..=FLASH_SIZE as i64).contains(&new_pos) {
+            return Err(ErrorKind::SeekOutOfRange.into());
+        }
+
+        self.position = new_pos as u64;
+        Ok(self.position)
+    }
+
+    /// Fill `buf` from flash starting at `addr`
+    ///
+    /// Reads whole chunks aligned to [`READ_CHUNK_SIZE`], so a read never
+    /// crosses the end of flash.
+    fn read_at(&mut self, addr: u32, buf: &mut [u8]) -> Result<(), Error> {
+        let mut total_read = 0;
+        while total_read < buf.len() {
+            let pos = addr as usize + total_read;
+            let offset = pos % READ_CHUNK_SIZE;
+            let chunk = self.read_chunk((pos - offset) as u32)?;
+            let len = (buf.len() - total_read).min(READ_CHUNK_SIZE - offset);
+            buf[total_read..total_read + len].copy_from_slice(&chunk[offset..offset + len]);
+            total_read += len;
+        }
+        Ok(())
+    }
+
+    /// Read flash from the current position, shared by the `embedded-io` and `std::io` impls
+    fn read_flash(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if self.position >= FLASH_SIZE as u64 {
+            return Ok(0);
+        }
+        let max_len = (FLASH_SIZE as u64 - self.position) as usize;
+        let to_read = buf.len().min(max_len);
+        self.read_at(self.position as u32, &mut buf[..to_read])?;
+        self.position += to_read as u64;
+        Ok(to_read)
+    }
+}
+
+impl<T, D> embedded_storage::nor_flash::ErrorType for Isd9160<T, D> {
+    type Error = Error;
+}
+
+/// Random access to the flash, independent of the `Read`/`Seek` position
+impl<T, D> embedded_storage::nor_flash::ReadNorFlash for Isd9160<T, D>
+where
+    T: I2c<Error: SourceError>,
+    D: DelayTrait,
+{
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Error> {
+        embedded_storage::nor_flash::check_read(self, offset, bytes.len())?;
+        self.read_at(offset, bytes)
+    }
+
+    fn capacity(&self) -> usize {
+        FLASH_SIZE
+    }
+}
 
-        buf[STATUS_PREFIX_SZ..].try_into().unwrap()
+impl<T, D> embedded_io::ErrorType for Isd9160<T, D> {
+    type Error = Error;
+}
+
+impl<T, D> embedded_io::Seek for Isd9160<T, D>
+where
+    T: I2c<Error: SourceError>,
+    D: DelayTrait,
+{
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Error> {
+        self.seek_flash(pos)
+    }
+}
+
+impl<T, D> embedded_io::Read for Isd9160<T, D>
+where
+    T: I2c<Error: SourceError>,
+    D: DelayTrait,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.read_flash(buf)
     }
 }
 
 #[cfg(feature = "std")]
-impl<T> std::io::Seek for Isd9160<T>
+impl<T, D> std::io::Seek for Isd9160<T, D>
 where
-    T: I2c
+    T: I2c<Error: SourceError>,
+    D: DelayTrait,
 {
     fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
-        use std::io::SeekFrom;
-        let new_pos = match pos {
-            SeekFrom::Start(offset) => offset,
-            SeekFrom::End(offset) => {
-                let end = FLASH_SIZE as i64;
-                let np = end.checked_add(offset).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "Seek out of bounds"))?;
-                if np < 0 { return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Seek before start")); }
-                np as u64
-            }
-            SeekFrom::Current(offset) => {
-                let cur = self.position as i64;
-                let np = cur.checked_add(offset).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "Seek out of bounds"))?;
-                if np < 0 { return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Seek before start")); }
-                np as u64
-            }
+        let pos = match pos {
+            std::io::SeekFrom::Start(offset) => SeekFrom::Start(offset),
+            std::io::SeekFrom::End(offset) => SeekFrom::End(offset),
+            std::io::SeekFrom::Current(offset) => SeekFrom::Current(offset),
         };
-        if new_pos > FLASH_SIZE as u64 {
-            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Seek past end of flash"));
-        }
-        self.position = new_pos;
-        Ok(self.position)
+        Ok(self.seek_flash(pos)?)
     }
 }
 
 #[cfg(feature = "std")]
-impl<T> std::io::Read for Isd9160<T>
+impl<T, D> std::io::Read for Isd9160<T, D>
 where
-    T: I2c
+    T: I2c<Error: SourceError>,
+    D: DelayTrait,
 {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        if self.position >= FLASH_SIZE as u64 {
-            return Ok(0);
+        Ok(self.read_flash(buf)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::NoDelay;
+    use core::convert::Infallible;
+    use embedded_hal::i2c::{ErrorType, NoAcknowledgeSource, Operation};
+
+    /// Serves flash reads from a fixed image
+    struct MockFlash {
+        image: [u8; FLASH_SIZE],
+        /// Status prefix sent with every chunk
+        status: u16,
+        /// Number of upcoming reads with a flipped bit
+        glitches: usize,
+        reads: usize,
+    }
+
+    impl MockFlash {
+        fn new() -> Self {
+            let mut image = [0u8; FLASH_SIZE];
+            for (i, byte) in image.iter_mut().enumerate() {
+                *byte = (i % 251) as u8;
+            }
+            Self {
+                image,
+                // Any word but all zeros or all ones passes the status check
+                status: 0x0100,
+                glitches: 0,
+                reads: 0,
+            }
         }
-        let max_len = (FLASH_SIZE as u64 - self.position) as usize;
-        let to_read = buf.len().min(max_len);
-        let mut total_read = 0;
-        while total_read < to_read {
-            let addr = self.position as u32;
-            let chunk = self.read_data(addr);
-            let chunk_start = 0;
-            let chunk_end = (to_read - total_read).min(READ_CHUNK_SIZE);
-            buf[total_read..total_read+chunk_end].copy_from_slice(&chunk[chunk_start..chunk_end]);
-            self.position += chunk_end as u64;
-            total_read += chunk_end;
-        }
-        Ok(total_read)
+    }
+
+    impl ErrorType for MockFlash {
+        type Error = Infallible;
+    }
+
+    impl I2c for MockFlash {
+        fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Infallible> {
+            assert_eq!(address, Isd9160::<Self, NoDelay>::I2C_ADDR);
+
+            let mut addr = 0;
+            for op in operations {
+                match op {
+                    Operation::Write(cmd) => {
+                        assert_eq!(cmd[0], u8::from(Isd9160Commands::CMD_FLASH_READ));
+                        addr = u32::from_le_bytes(cmd[1..5].try_into().unwrap()) as usize;
+                    }
+                    Operation::Read(buf) => {
+                        assert_eq!(buf.len(), READ_CHUNK_SIZE + STATUS_PREFIX_SZ);
+                        assert_eq!(addr % READ_CHUNK_SIZE, 0);
+                        buf[..STATUS_PREFIX_SZ].copy_from_slice(&self.status.to_le_bytes());
+                        buf[STATUS_PREFIX_SZ..].copy_from_slice(&self.image[addr..addr + READ_CHUNK_SIZE]);
+                        if self.glitches > 0 {
+                            buf[STATUS_PREFIX_SZ + 5] ^= 1 << (self.glitches % 8);
+                            self.glitches -= 1;
+                        }
+                        self.reads += 1;
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_embedded_io_read_seek() {
+        use embedded_io::{Read, Seek};
+
+        let mut isd = Isd9160::new(MockFlash::new(), NoDelay);
+
+        assert_eq!(isd.seek(SeekFrom::Start(100)).unwrap(), 100);
+        let mut buf = [0u8; 150];
+        isd.read_exact(&mut buf).unwrap();
+        assert!(buf.iter().enumerate().all(|(i, &b)| b == ((100 + i) % 251) as u8));
+
+        // Reads stop at the end of flash
+        assert_eq!(isd.seek(SeekFrom::End(-10)).unwrap(), FLASH_SIZE as u64 - 10);
+        assert_eq!(isd.read(&mut buf).unwrap(), 10);
+        assert_eq!(isd.read(&mut buf).unwrap(), 0);
+
+        assert!(matches!(isd.seek(SeekFrom::End(1)).map_err(Error::into_kind), Err(ErrorKind::SeekOutOfRange)));
+        assert!(matches!(isd.seek(SeekFrom::Current(-(FLASH_SIZE as i64) - 1)).map_err(Error::into_kind), Err(ErrorKind::SeekOutOfRange)));
+    }
+
+    #[test]
+    fn test_read_nor_flash() {
+        use embedded_storage::nor_flash::ReadNorFlash;
+
+        let mut isd = Isd9160::new(MockFlash::new(), NoDelay);
+        assert_eq!(isd.capacity(), FLASH_SIZE);
+
+        let mut buf = [0u8; 100];
+        ReadNorFlash::read(&mut isd, 1000, &mut buf).unwrap();
+        assert!(buf.iter().enumerate().all(|(i, &b)| b == ((1000 + i) % 251) as u8));
+
+        let end = (FLASH_SIZE - buf.len()) as u32;
+        assert!(ReadNorFlash::read(&mut isd, end, &mut buf).is_ok());
+        assert!(matches!(
+            ReadNorFlash::read(&mut isd, end + 1, &mut buf).map_err(Error::into_kind),
+            Err(ErrorKind::OutOfBounds)
+        ));
+    }
+
+    #[test]
+    fn test_full_chunk_read() {
+        // One transfer returns a whole 64-byte chunk behind the status prefix
+        let mut isd = Isd9160::new(MockFlash::new(), NoDelay);
+        let chunk = isd.read_data(READ_CHUNK_SIZE as u32).unwrap();
+        assert_eq!(chunk[..], isd.device.image[READ_CHUNK_SIZE..2 * READ_CHUNK_SIZE]);
+        assert_eq!(isd.device.reads, 1);
+    }
+
+    #[test]
+    fn test_status_prefix() {
+        use embedded_storage::nor_flash::ReadNorFlash;
+
+        let mut isd = Isd9160::new(MockFlash::new(), NoDelay);
+        let mut buf = [0u8; 4];
+        ReadNorFlash::read(&mut isd, 0, &mut buf).unwrap();
+        assert_eq!(buf, isd.device.image[..4]);
+
+        // Undriven or shorted lines can't be a status
+        for status in [0x0000, 0xFFFF] {
+            isd.device.status = status;
+            let err = ReadNorFlash::read(&mut isd, 0, &mut buf).unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::InvalidReadStatus { status: s } if *s == status));
+        }
+
+        // The raw word is handed out unchecked
+        let (status, chunk) = isd.read_chunk_with_status(READ_CHUNK_SIZE as u32).unwrap();
+        assert_eq!(status, 0xFFFF);
+        assert_eq!(chunk[..], isd.device.image[READ_CHUNK_SIZE..2 * READ_CHUNK_SIZE]);
+        assert!(matches!(
+            isd.read_chunk_with_status(1).map_err(Error::into_kind),
+            Err(ErrorKind::NotAligned)
+        ));
+    }
+
+    #[test]
+    fn test_double_read() {
+        use embedded_storage::nor_flash::ReadNorFlash;
+
+        let expected: [u8; READ_CHUNK_SIZE] = core::array::from_fn(|i| i as u8);
+        let mut buf = [0u8; READ_CHUNK_SIZE];
+
+        let mut flash = MockFlash::new();
+        flash.glitches = 1;
+        let mut isd = Isd9160::new(flash, NoDelay);
+        ReadNorFlash::read(&mut isd, 0, &mut buf).unwrap();
+        assert_ne!(buf, expected);
+
+        // A glitched read is outvoted by the next two
+        isd.device.glitches = 1;
+        isd.device.reads = 0;
+        let mut isd = isd.with_verify(VerifyMode::DoubleRead);
+        ReadNorFlash::read(&mut isd, 0, &mut buf).unwrap();
+        assert_eq!(buf, expected);
+        assert_eq!(isd.device.reads, 3);
+
+        // Every read differs from the previous one
+        isd.device.glitches = VERIFY_READS;
+        let err = ReadNorFlash::read(&mut isd, 64, &mut buf).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::VerifyMismatch { address: 64 }));
+    }
+
+    /// NACKs the address for the first `nacks` transactions and records writes
+    struct FlakyBus {
+        nacks: usize,
+        writes: Vec<Vec<u8>>,
+    }
+
+    impl ErrorType for FlakyBus {
+        type Error = I2cErrorKind;
+    }
+
+    impl I2c for FlakyBus {
+        fn transaction(&mut self, _address: u8, operations: &mut [Operation<'_>]) -> Result<(), I2cErrorKind> {
+            if self.nacks > 0 {
+                self.nacks -= 1;
+                return Err(I2cErrorKind::NoAcknowledge(NoAcknowledgeSource::Address));
+            }
+            for op in operations {
+                match op {
+                    Operation::Write(bytes) => self.writes.push(bytes.to_vec()),
+                    Operation::Read(buf) => buf.fill(0xAA),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_register_padding() {
+        let mut isd = Isd9160::new(FlakyBus { nacks: 0, writes: Vec::new() }, NoDelay);
+        isd.init().unwrap();
+        assert_eq!(isd.device.writes, [[0x48, 0x0C, 0x01, 0, 0, 0], [0x48, 0x04, 0xFF, 0xFF, 0, 0]]);
+
+        let err = isd.write_register(Isd9160Registers::REG_CTL, &[0; 5]).unwrap_err();
+        assert_eq!(err.context().register, Some(0x00));
+        assert!(matches!(err.kind(), ErrorKind::OutOfBounds));
+    }
+
+    #[test]
+    fn test_nack_retry() {
+        /// Sums up the requested delays
+        struct TotalDelay(u64);
+
+        impl DelayTrait for TotalDelay {
+            fn delay_ns(&mut self, ns: u32) {
+                self.0 += u64::from(ns);
+            }
+        }
+
+        let bus = FlakyBus { nacks: DEFAULT_NACK_RETRIES as usize, writes: Vec::new() };
+        let mut isd = Isd9160::new(bus, TotalDelay(0));
+        assert_eq!(isd.read_register(Isd9160Registers::REG_STATUS).unwrap(), 0xAAAAAAAA);
+        let interval = DEFAULT_NACK_RETRY_INTERVAL.as_nanos() as u64;
+        assert_eq!(isd.delay.0, u64::from(DEFAULT_NACK_RETRIES) * interval);
+
+        let mut isd = Isd9160::new(FlakyBus { nacks: 2, writes: Vec::new() }, NoDelay).with_nack_retries(1, Duration::ZERO);
+        let err = isd.read_register(Isd9160Registers::REG_STATUS).unwrap_err();
+        assert!(err.is_transient());
+        assert_eq!(err.context().register, Some(0x0C));
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::I2c(I2cErrorKind::NoAcknowledge(NoAcknowledgeSource::Address))
+        ));
+        assert_eq!(isd.device.nacks, 0);
+    }
+
+    #[test]
+    fn test_io_read_error() {
+        use std::io::Read;
+
+        let mut isd = Isd9160::new(FlakyBus { nacks: 10, writes: Vec::new() }, NoDelay).with_nack_retries(0, Duration::ZERO);
+        let mut buf = [0u8; 16];
+        let err = isd.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Other);
+        assert!(err.to_string().starts_with("I2C error"));
+
+        // The position only moves on success
+        isd.device.nacks = 0;
+        assert_eq!(isd.read(&mut buf).unwrap(), 16);
+        assert_eq!(isd.position, 16);
     }
 }
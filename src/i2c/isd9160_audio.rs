@@ -0,0 +1,186 @@
+//! Audio codecs for ISD9160 sound resources
+//!
+//! G.711 u-law and IMA ADPCM, plus a WAV writer for decoded samples. How the
+//! chip firmware stores its sounds in flash is not known yet, so nothing here
+//! parses flash images.
+
+use crate::prelude::*;
+use crate::error::Error;
+
+/// Decode a G.711 u-law byte
+pub fn ulaw_decode(byte: u8) -> i16 {
+    const BIAS: i16 = 0x84;
+
+    let byte = !byte;
+    let exponent = (byte >> 4) & 0x07;
+    let mantissa = (byte & 0x0F) as i16;
+    let magnitude = ((mantissa << 3) + BIAS) << exponent;
+    if byte & 0x80 != 0 {
+        BIAS - magnitude
+    } else {
+        magnitude - BIAS
+    }
+}
+
+/// Encode a sample as G.711 u-law
+pub fn ulaw_encode(sample: i16) -> u8 {
+    const BIAS: i32 = 0x84;
+    const CLIP: i32 = 32635;
+
+    let sample = i32::from(sample);
+    let sign = if sample < 0 { 0x80 } else { 0 };
+    let magnitude = sample.abs().min(CLIP) + BIAS;
+    let exponent = 31 - ((magnitude >> 7) as u32).leading_zeros();
+    let mantissa = (magnitude >> (exponent + 3)) & 0x0F;
+    !(sign | (exponent << 4) as u8 | mantissa as u8)
+}
+
+const IMA_STEP_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+    73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408,
+    449, 494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066,
+    2272, 2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630,
+    9493, 10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794,
+    32767,
+];
+
+const IMA_INDEX_TABLE: [i32; 8] = [-1, -1, -1, -1, 2, 4, 6, 8];
+
+/// IMA ADPCM predictor state, starting at silence for every sound
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImaAdpcm {
+    predictor: i32,
+    index: i32,
+}
+
+impl ImaAdpcm {
+    /// Decode one 4-bit code
+    pub fn decode(&mut self, code: u8) -> i16 {
+        let step = IMA_STEP_TABLE[self.index as usize];
+        let mut diff = step >> 3;
+        if code & 4 != 0 {
+            diff += step;
+        }
+        if code & 2 != 0 {
+            diff += step >> 1;
+        }
+        if code & 1 != 0 {
+            diff += step >> 2;
+        }
+        self.step(code, diff)
+    }
+
+    /// Encode one sample, returning the 4-bit code
+    pub fn encode(&mut self, sample: i16) -> u8 {
+        let step = IMA_STEP_TABLE[self.index as usize];
+        let mut delta = i32::from(sample) - self.predictor;
+        let mut code = 0;
+        if delta < 0 {
+            code = 8;
+            delta = -delta;
+        }
+
+        let mut diff = step >> 3;
+        if delta >= step {
+            code |= 4;
+            delta -= step;
+            diff += step;
+        }
+        if delta >= step >> 1 {
+            code |= 2;
+            delta -= step >> 1;
+            diff += step >> 1;
+        }
+        if delta >= step >> 2 {
+            code |= 1;
+            diff += step >> 2;
+        }
+        self.step(code, diff);
+        code
+    }
+
+    /// Apply a code with its difference, shared with the encoder
+    fn step(&mut self, code: u8, diff: i32) -> i16 {
+        self.predictor = if code & 8 != 0 {
+            self.predictor - diff
+        } else {
+            self.predictor + diff
+        }
+        .clamp(i16::MIN as i32, i16::MAX as i32);
+        self.index = (self.index + IMA_INDEX_TABLE[(code & 7) as usize]).clamp(0, 88);
+        self.predictor as i16
+    }
+}
+
+/// Write mono 16-bit PCM samples as a WAV file
+#[cfg(feature = "std")]
+pub fn write_wav<W: std::io::Write>(
+    out: &mut W,
+    sample_rate: u32,
+    samples: impl ExactSizeIterator<Item = i16>,
+) -> Result<(), Error> {
+    let data_len = (samples.len() * 2) as u32;
+
+    out.write_all(b"RIFF")?;
+    out.write_all(&(36 + data_len).to_le_bytes())?;
+    out.write_all(b"WAVEfmt ")?;
+    out.write_all(&16u32.to_le_bytes())?;
+    // PCM, mono
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&sample_rate.to_le_bytes())?;
+    out.write_all(&(sample_rate * 2).to_le_bytes())?;
+    // Block align, bits per sample
+    out.write_all(&2u16.to_le_bytes())?;
+    out.write_all(&16u16.to_le_bytes())?;
+    out.write_all(b"data")?;
+    out.write_all(&data_len.to_le_bytes())?;
+    for sample in samples {
+        out.write_all(&sample.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ulaw() {
+        assert_eq!(ulaw_decode(0xFF), 0);
+        assert_eq!(ulaw_decode(0x80), 32124);
+        assert_eq!(ulaw_decode(0x00), -32124);
+
+        assert_eq!(ulaw_encode(0), 0xFF);
+        assert_eq!(ulaw_encode(i16::MAX), 0x80);
+        assert_eq!(ulaw_encode(i16::MIN), 0x00);
+        assert!((0..=255).all(|byte| ulaw_encode(ulaw_decode(byte)) == byte || byte == 0x7F));
+    }
+
+    #[test]
+    fn test_ima_adpcm_roundtrip() {
+        let mut encoder = ImaAdpcm::default();
+        let mut decoder = ImaAdpcm::default();
+        for i in 0..400 {
+            let sample = (10000.0 * (i as f64 / 20.0).sin()) as i16;
+            let decoded = decoder.decode(encoder.encode(sample));
+            assert_eq!(encoder, decoder);
+            if i > 20 {
+                assert!((decoded - sample).abs() < 1000, "sample {i}: {decoded} vs {sample}");
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_write_wav() {
+        let samples: Vec<i16> = (0..100).map(|i| i * 300 - 15000).collect();
+        let mut wav = Vec::new();
+        write_wav(&mut wav, 8000, samples.iter().copied()).unwrap();
+
+        assert_eq!(wav.len(), 44 + 200);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), 8000);
+        assert_eq!(&wav[44..46], &(-15000i16).to_le_bytes());
+    }
+}
@@ -0,0 +1,266 @@
+//! Register-level configuration of the ISD9160
+//!
+//! The registers in [`Isd9160Registers`] belong to the I2C peripheral of the
+//! chip, the bit layouts follow the Nuvoton ISD9160 technical reference
+//! manual. Slot 0 holds the address the console talks to, so only the
+//! secondary slots 1 to 3 can be changed.
+
+use embedded_hal::i2c::I2c;
+use crate::prelude::*;
+use super::isd9160::{Isd9160, Isd9160Commands, Isd9160Registers};
+use crate::error::{Error, ErrorKind, SourceError};
+use crate::DelayTrait;
+
+/// Number of slave address slots
+pub const ADDRESS_SLOTS: usize = 4;
+
+const ADDRESS_REGISTERS: [Isd9160Registers; ADDRESS_SLOTS] = [
+    Isd9160Registers::REG_ADDR0,
+    Isd9160Registers::REG_ADDR1,
+    Isd9160Registers::REG_ADDR2,
+    Isd9160Registers::REG_ADDR3,
+];
+
+const MASK_REGISTERS: [Isd9160Registers; ADDRESS_SLOTS] = [
+    Isd9160Registers::REG_ADDRMSK0,
+    Isd9160Registers::REG_ADDRMSK1,
+    Isd9160Registers::REG_ADDRMSK2,
+    Isd9160Registers::REG_ADDRMSK3,
+];
+
+bitflags::bitflags! {
+    /// `REG_CTL`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct ControlFlags: u32 {
+        /// Acknowledge when addressed or receiving data
+        const AA = 1 << 2;
+        /// Interrupt flag, a bus event needs service
+        const SI = 1 << 3;
+        const STO = 1 << 4;
+        const STA = 1 << 5;
+        /// Peripheral enabled
+        const ENSI = 1 << 6;
+        /// Interrupt enabled
+        const EI = 1 << 7;
+    }
+}
+
+bitflags::bitflags! {
+    /// `REG_TOCTL`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct TimeoutControl: u32 {
+        /// Timeout occurred, write 1 to clear
+        const TIF = 1 << 0;
+        /// Timeout counter clocked by PCLK / 4
+        const DIV4 = 1 << 1;
+        /// Timeout counter enabled
+        const ENTI = 1 << 2;
+    }
+}
+
+/// Entry of an address slot, `REG_ADDRx` with its `REG_ADDRMSKx`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SlaveAddress {
+    /// 7-bit address
+    pub address: u8,
+    /// Also respond to the general call address 0x00
+    pub general_call: bool,
+    /// Address bits set here are ignored when matching
+    pub mask: u8,
+}
+
+impl SlaveAddress {
+    fn from_registers(address: u32, mask: u32) -> Self {
+        Self {
+            address: ((address >> 1) & 0x7F) as u8,
+            general_call: address & 1 != 0,
+            mask: ((mask >> 1) & 0x7F) as u8,
+        }
+    }
+
+    fn address_register(&self) -> u32 {
+        (u32::from(self.address) << 1) | u32::from(self.general_call)
+    }
+
+    fn mask_register(&self) -> u32 {
+        u32::from(self.mask) << 1
+    }
+}
+
+/// Snapshot of the configuration registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Isd9160Config {
+    pub control: ControlFlags,
+    /// Bus clock is PCLK / (4 * (divider + 1)), the whole `REG_CLKDIV`
+    pub clock_divider: u32,
+    pub timeout: TimeoutControl,
+    pub addresses: [SlaveAddress; ADDRESS_SLOTS],
+}
+
+impl core::fmt::Display for Isd9160Config {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "REG_CTL:    {:#04x} {:?}", self.control.bits(), self.control)?;
+        writeln!(f, "REG_CLKDIV: {:#04x} (PCLK / {})", self.clock_divider, 4 * (u64::from(self.clock_divider) + 1))?;
+        writeln!(f, "REG_TOCTL:  {:#04x} {:?}", self.timeout.bits(), self.timeout)?;
+        for (slot, entry) in self.addresses.iter().enumerate() {
+            write!(f, "REG_ADDR{slot}:  {:#04x} mask {:#04x}", entry.address, entry.mask)?;
+            if entry.general_call {
+                write!(f, " +general call")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T, D> Isd9160<T, D>
+where
+    T: I2c<Error: SourceError>,
+    D: DelayTrait,
+{
+    /// Write a whole register
+    pub fn write_register_u32<U: Into<u8>>(&mut self, register: U, value: u32) -> Result<(), Error> {
+        self.write_register(register, &value.to_le_bytes())
+    }
+
+    /// Read a register, apply `f` and write the result back, returning it
+    pub fn modify_register<F>(&mut self, register: Isd9160Registers, f: F) -> Result<u32, Error>
+    where
+        F: FnOnce(u32) -> u32,
+    {
+        let value = f(self.read_register(register)?);
+        self.write_register_u32(register, value)?;
+        Ok(value)
+    }
+
+    pub fn read_config(&mut self) -> Result<Isd9160Config, Error> {
+        let mut addresses = [SlaveAddress::default(); ADDRESS_SLOTS];
+        for (slot, entry) in addresses.iter_mut().enumerate() {
+            *entry = SlaveAddress::from_registers(
+                self.read_register(ADDRESS_REGISTERS[slot])?,
+                self.read_register(MASK_REGISTERS[slot])?,
+            );
+        }
+
+        Ok(Isd9160Config {
+            control: ControlFlags::from_bits_retain(self.read_register(Isd9160Registers::REG_CTL)?),
+            clock_divider: self.read_register(Isd9160Registers::REG_CLKDIV)?,
+            timeout: TimeoutControl::from_bits_retain(self.read_register(Isd9160Registers::REG_TOCTL)?),
+            addresses,
+        })
+    }
+
+    pub fn set_clock_divider(&mut self, divider: u8) -> Result<(), Error> {
+        self.write_register_u32(Isd9160Registers::REG_CLKDIV, u32::from(divider))
+    }
+
+    pub fn set_timeout_control(&mut self, timeout: TimeoutControl) -> Result<(), Error> {
+        self.write_register_u32(Isd9160Registers::REG_TOCTL, timeout.bits())
+    }
+
+    /// Configure a secondary address slot (1 to 3)
+    pub fn set_slave_address(&mut self, slot: usize, entry: SlaveAddress) -> Result<(), Error> {
+        if !(1..ADDRESS_SLOTS).contains(&slot) || entry.address > 0x7F || entry.mask > 0x7F {
+            return Err(ErrorKind::OutOfBounds.into());
+        }
+        self.write_register_u32(ADDRESS_REGISTERS[slot], entry.address_register())?;
+        self.write_register_u32(MASK_REGISTERS[slot], entry.mask_register())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+    use embedded_hal::i2c::{ErrorType, Operation};
+    use crate::fixtures::NoDelay;
+
+    /// Register file behind the register read and write commands
+    struct MockRegisters {
+        registers: [u32; 0x34 / 4],
+    }
+
+    impl ErrorType for MockRegisters {
+        type Error = Infallible;
+    }
+
+    impl I2c for MockRegisters {
+        fn transaction(&mut self, _address: u8, operations: &mut [Operation<'_>]) -> Result<(), Infallible> {
+            match operations {
+                [Operation::Write(cmd)] if cmd[0] == Isd9160Commands::CMD_REG_WRITE as u8 => {
+                    self.registers[cmd[1] as usize / 4] = u32::from_le_bytes(cmd[2..6].try_into().unwrap());
+                }
+                [Operation::Write(cmd), Operation::Read(buf)] if cmd[0] == Isd9160Commands::CMD_REG_READ as u8 => {
+                    buf.copy_from_slice(&self.registers[cmd[1] as usize / 4].to_le_bytes());
+                }
+                _ => panic!("unexpected transaction"),
+            }
+            Ok(())
+        }
+    }
+
+    fn isd() -> Isd9160<MockRegisters, NoDelay> {
+        let mut registers = [0; 0x34 / 4];
+        registers[0] = 0x44;
+        registers[1] = 0x5A << 1;
+        Isd9160::new(MockRegisters { registers }, NoDelay)
+    }
+
+    #[test]
+    fn test_modify_register() {
+        let mut isd = isd();
+        let value = isd
+            .modify_register(Isd9160Registers::REG_CTL, |ctl| ctl | ControlFlags::EI.bits())
+            .unwrap();
+        assert_eq!(value, 0xC4);
+        assert_eq!(isd.read_register(Isd9160Registers::REG_CTL).unwrap(), 0xC4);
+    }
+
+    #[test]
+    fn test_config() {
+        let mut isd = isd();
+        isd.set_clock_divider(9).unwrap();
+        isd.set_timeout_control(TimeoutControl::ENTI | TimeoutControl::DIV4).unwrap();
+        let secondary = SlaveAddress { address: 0x2A, general_call: true, mask: 0x01 };
+        isd.set_slave_address(2, secondary).unwrap();
+        assert!(matches!(
+            isd.set_slave_address(0, secondary).map_err(Error::into_kind),
+            Err(ErrorKind::OutOfBounds)
+        ));
+
+        let config = isd.read_config().unwrap();
+        assert_eq!(config.control, ControlFlags::AA | ControlFlags::ENSI);
+        assert_eq!(config.clock_divider, 9);
+        assert_eq!(config.addresses[0].address, Isd9160::<MockRegisters, NoDelay>::I2C_ADDR);
+        assert_eq!(config.addresses[2], secondary);
+        assert_eq!(isd.device().registers[0x1C / 4], 0x55);
+
+        let text = format!("{config}");
+        assert!(text.contains("REG_CLKDIV: 0x09 (PCLK / 40)\n"));
+        assert!(text.contains("REG_ADDR2:  0x2a mask 0x01 +general call\n"));
+    }
+
+    #[test]
+    fn test_slave_address_validation() {
+        let mut isd = isd();
+        for entry in [
+            SlaveAddress { address: 0x80, ..Default::default() },
+            SlaveAddress { address: 0x2A, mask: 0x80, ..Default::default() },
+        ] {
+            assert!(matches!(
+                isd.set_slave_address(1, entry).map_err(Error::into_kind),
+                Err(ErrorKind::OutOfBounds)
+            ));
+        }
+        assert_eq!(isd.device().registers[0x18 / 4], 0);
+    }
+
+    #[test]
+    fn test_clock_divider_not_truncated() {
+        let mut isd = isd();
+        isd.write_register_u32(Isd9160Registers::REG_CLKDIV, 0x109).unwrap();
+        let config = isd.read_config().unwrap();
+        assert_eq!(config.clock_divider, 0x109);
+        assert!(format!("{config}").contains("REG_CLKDIV: 0x109 (PCLK / 1064)\n"));
+    }
+}
@@ -0,0 +1,149 @@
+//! WAV input for ISD9160 sound resources
+//!
+//! Parses PCM WAV files and resamples them to the rates the chip plays.
+//! Building flash images from them waits for the sound table layout of a
+//! retail dump.
+
+use crate::prelude::*;
+use crate::error::{Error, ErrorKind};
+
+/// Decoded WAV audio, downmixed to mono
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WavAudio {
+    pub sample_rate: u32,
+    pub samples: Vec<i16>,
+}
+
+impl WavAudio {
+    /// Parse a PCM WAV file with 8 or 16 bits per sample
+    pub fn parse(wav: &[u8]) -> Result<Self, Error> {
+        let invalid = |reason| Error::from(ErrorKind::InvalidWav { reason });
+
+        if wav.len() < 12 || &wav[..4] != b"RIFF" || &wav[8..12] != b"WAVE" {
+            return Err(invalid("not a RIFF/WAVE file"));
+        }
+
+        let mut format = None;
+        let mut position = 12;
+        while let Some(header) = wav.get(position..position + 8) {
+            let id = &header[..4];
+            let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+            let body = wav
+                .get(position + 8..position + 8 + size)
+                .ok_or_else(|| invalid("truncated chunk"))?;
+
+            match id {
+                b"fmt " => {
+                    if body.len() < 16 {
+                        return Err(invalid("short fmt chunk"));
+                    }
+                    let tag = u16::from_le_bytes([body[0], body[1]]);
+                    let channels = u16::from_le_bytes([body[2], body[3]]) as usize;
+                    let sample_rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+                    let bits = u16::from_le_bytes([body[14], body[15]]);
+                    if tag != 1 {
+                        return Err(invalid("not PCM"));
+                    }
+                    if channels == 0 || sample_rate == 0 || !matches!(bits, 8 | 16) {
+                        return Err(invalid("unsupported sample format"));
+                    }
+                    format = Some((channels, sample_rate, bits));
+                }
+                b"data" => {
+                    let (channels, sample_rate, bits) =
+                        format.ok_or_else(|| invalid("data before fmt chunk"))?;
+                    let frame_size = channels * usize::from(bits / 8);
+                    let samples = body
+                        .chunks_exact(frame_size)
+                        .map(|frame| {
+                            let sum: i32 = if bits == 8 {
+                                frame.iter().map(|&s| (i32::from(s) - 128) << 8).sum()
+                            } else {
+                                frame
+                                    .chunks_exact(2)
+                                    .map(|s| i32::from(i16::from_le_bytes([s[0], s[1]])))
+                                    .sum()
+                            };
+                            (sum / channels as i32) as i16
+                        })
+                        .collect();
+                    return Ok(Self {
+                        sample_rate,
+                        samples,
+                    });
+                }
+                _ => {}
+            }
+            // Chunks are padded to even sizes
+            position += 8 + size + size % 2;
+        }
+        Err(invalid("missing data chunk"))
+    }
+
+    /// Resample with linear interpolation
+    pub fn resample(&self, sample_rate: u32) -> Vec<i16> {
+        if sample_rate == self.sample_rate || self.samples.is_empty() {
+            return self.samples.clone();
+        }
+
+        let count = (self.samples.len() as u64 * u64::from(sample_rate) / u64::from(self.sample_rate)) as usize;
+        let last = self.samples.len() - 1;
+        (0..count)
+            .map(|i| {
+                // Source position in 1/sample_rate units to stay exact
+                let position = i as u64 * u64::from(self.sample_rate);
+                let index = (position / u64::from(sample_rate)) as usize;
+                let fraction = (position % u64::from(sample_rate)) as i64;
+                let a = i64::from(self.samples[index.min(last)]);
+                let b = i64::from(self.samples[(index + 1).min(last)]);
+                (a + (b - a) * fraction / i64::from(sample_rate)) as i16
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i2c::isd9160_audio::write_wav;
+
+    fn sine(sample_rate: u32, count: usize) -> Vec<i16> {
+        (0..count)
+            .map(|i| (10000.0 * (2.0 * core::f64::consts::PI * 440.0 * i as f64 / sample_rate as f64).sin()) as i16)
+            .collect()
+    }
+
+    #[test]
+    fn test_wav_parse() {
+        let samples = sine(22050, 300);
+        let mut wav = Vec::new();
+        write_wav(&mut wav, 22050, samples.iter().copied()).unwrap();
+        let audio = WavAudio::parse(&wav).unwrap();
+        assert_eq!(audio.sample_rate, 22050);
+        assert_eq!(audio.samples, samples);
+
+        // 8-bit stereo, both channels averaged
+        let mut wav = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+        wav.extend_from_slice(&[16, 0, 0, 0, 1, 0, 2, 0, 0x40, 0x1F, 0, 0, 0x80, 0x3E, 0, 0, 2, 0, 8, 0]);
+        wav.extend_from_slice(b"data\x04\0\0\0");
+        wav.extend_from_slice(&[0x80, 0xFF, 0x00, 0x80]);
+        let audio = WavAudio::parse(&wav).unwrap();
+        assert_eq!(audio.sample_rate, 8000);
+        assert_eq!(audio.samples, [127 << 7, -128 << 7]);
+
+        assert!(matches!(
+            WavAudio::parse(&wav[..40]).map_err(Error::into_kind),
+            Err(ErrorKind::InvalidWav { .. })
+        ));
+    }
+
+    #[test]
+    fn test_resample() {
+        let audio = WavAudio {
+            sample_rate: 16000,
+            samples: vec![0, 100, 200, 300],
+        };
+        assert_eq!(audio.resample(8000), [0, 200]);
+        assert_eq!(audio.resample(32000), [0, 50, 100, 150, 200, 250, 300, 300]);
+    }
+}
@@ -1,3 +1,10 @@
+pub mod bitbang;
 #[cfg(feature = "ftdi")]
 pub mod i2c_bitbang;
-pub mod isd9160;
\ No newline at end of file
+#[cfg(feature = "ftdi")]
+pub mod i2c_mpsse;
+pub mod isd9160;
+pub mod isd9160_audio;
+pub mod isd9160_config;
+#[cfg(feature = "std")]
+pub mod isd9160_wav;
@@ -16,16 +16,30 @@ pub use no_std_prelude::no_std_prelude as prelude;
 #[cfg(feature = "std")]
 pub use std_prelude::prelude;
 
+#[cfg(feature = "ftdi")]
+pub mod board;
+#[cfg(feature = "sdmmc")]
+pub mod block_device;
 pub mod error;
 pub mod i2c;
+pub mod pinmap;
 pub mod spi;
 
+#[cfg(test)]
+mod fixtures;
+
 pub use embedded_hal;
 pub use embedded_hal::delay::DelayNs as DelayTrait;
 
+pub use i2c::bitbang::BitbangI2c;
 pub use i2c::isd9160::{Isd9160, Isd9160Sounds};
+pub use pinmap::PinMap;
+#[cfg(feature = "ftdi")]
+pub use board::Facet2Board;
 #[cfg(feature = "ftdi")]
 pub use i2c::i2c_bitbang::I2cFtBitbang;
 #[cfg(feature = "ftdi")]
+pub use i2c::i2c_mpsse::I2cFtMpsse;
+#[cfg(feature = "ftdi")]
 pub use libftd2xx::{BitMode, Ft4232h, FtdiCommon};
 
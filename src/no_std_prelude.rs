@@ -1,5 +1,3 @@
-#![no_std]
-
 pub mod no_std_prelude {
     pub use core::prelude::*;
     pub use core::prelude::rust_2024::derive;
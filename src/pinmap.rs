@@ -0,0 +1,392 @@
+//! GPIO pin maps for the FT4232H channels
+//!
+//! Each FT4232H channel exposes eight GPIO lines (xDBUS0..xDBUS7). Which line
+//! carries which signal depends on the board revision, so backends take a
+//! [`PinMap`] at construction instead of hardcoding bit masks.
+//!
+//! The FACET2 wiring is available as [`PinMap::facet2_spi`],
+//! [`PinMap::facet2_i2c`] and [`PinMap::facet2_jtag`].
+use core::fmt;
+
+use crate::prelude::*;
+use crate::error::{Error, ErrorKind};
+
+/// Number of GPIO lines on a single FT4232H channel
+pub const LINE_COUNT: usize = 8;
+
+/// Signal carried by a GPIO line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// SPI clock (MPSSE TCK/SK)
+    SpiClk,
+    /// SPI data out (MPSSE TDI/DO)
+    SpiMosi,
+    /// SPI data in (MPSSE TDO/DI)
+    SpiMiso,
+    /// SPI chip select (active low)
+    SpiSsN,
+    /// SPI level shifter enable (active low)
+    SpiEnN,
+    /// eMMC controller reset (active low)
+    SpiRstN,
+    /// SWO debug enable
+    SwoDbgEn,
+
+    /// I2C clock (open-drain)
+    I2cScl,
+    /// I2C data (open-drain)
+    I2cSda,
+
+    /// JTAG clock (MPSSE TCK)
+    JtagTck,
+    /// JTAG data in (MPSSE TDI)
+    JtagTdi,
+    /// JTAG data out (MPSSE TDO)
+    JtagTdo,
+    /// JTAG mode select (MPSSE TMS)
+    JtagTms,
+    /// JTAG level shifter enable
+    JtagEn,
+    /// JTAG reset (active low)
+    JtagRstN,
+}
+
+impl Signal {
+    /// Direction the signal has to be configured with
+    pub const fn direction(self) -> Direction {
+        match self {
+            Self::SpiMiso | Self::JtagTdo => Direction::Input,
+            Self::I2cScl | Self::I2cSda => Direction::OpenDrain,
+            _ => Direction::Output,
+        }
+    }
+
+    /// GPIO line the MPSSE engine hardwires this signal to, if any
+    ///
+    /// Clock and data lines are driven by the MPSSE engine itself and
+    /// cannot be moved to another line.
+    pub const fn mpsse_line(self) -> Option<u8> {
+        match self {
+            Self::SpiClk | Self::JtagTck => Some(0),
+            Self::SpiMosi | Self::JtagTdi => Some(1),
+            Self::SpiMiso | Self::JtagTdo => Some(2),
+            Self::JtagTms => Some(3),
+            _ => None,
+        }
+    }
+}
+
+/// GPIO line direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Line is always an input
+    Input,
+    /// Line is always a push-pull output
+    Output,
+    /// Line is emulated open-drain (driven low as output, released as input)
+    OpenDrain,
+}
+
+/// Assignment of a signal to a GPIO line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PinAssignment {
+    /// Signal carried by the line
+    pub signal: Signal,
+    /// GPIO line number (0-7)
+    pub line: u8,
+    /// Configured direction
+    pub direction: Direction,
+    /// Level the line is set to while idle
+    pub idle_high: bool,
+}
+
+impl PinAssignment {
+    /// Create an assignment with the direction required by the signal
+    pub const fn new(signal: Signal, line: u8, idle_high: bool) -> Self {
+        Self {
+            signal,
+            line,
+            direction: signal.direction(),
+            idle_high,
+        }
+    }
+
+    /// Single-bit mask of the line
+    pub fn mask(&self) -> u8 {
+        1 << self.line
+    }
+}
+
+/*
+FACET2 pin assignments on FT4232H:
+
+Channel A (eMMC SPI)     Channel B (SB JTAG)      Channel C (I2C)
+SPI_CLK:   AD0           TCK:        BD0          I2C_SCL: CD6
+SPI_MOSI:  AD1           TDI:        BD1          I2C_SDA: CD7
+SPI_MISO:  AD2           TDO:        BD2
+SPI_SS_N:  AD3           TMS:        BD3
+SPI_EN_N:  AD5           JTAG_EN:    BD5
+SPI_RST_N: AD7           JTAG_RST_N: BD7
+*/
+
+const FACET2_SPI: [PinAssignment; 6] = [
+    PinAssignment::new(Signal::SpiClk, 0, false),
+    PinAssignment::new(Signal::SpiMosi, 1, false),
+    PinAssignment::new(Signal::SpiMiso, 2, false),
+    PinAssignment::new(Signal::SpiSsN, 3, true),
+    PinAssignment::new(Signal::SpiEnN, 5, true),
+    PinAssignment::new(Signal::SpiRstN, 7, true),
+];
+
+const FACET2_I2C: [PinAssignment; 2] = [
+    PinAssignment::new(Signal::I2cScl, 6, true),
+    PinAssignment::new(Signal::I2cSda, 7, true),
+];
+
+const FACET2_JTAG: [PinAssignment; 6] = [
+    PinAssignment::new(Signal::JtagTck, 0, false),
+    PinAssignment::new(Signal::JtagTdi, 1, false),
+    PinAssignment::new(Signal::JtagTdo, 2, false),
+    PinAssignment::new(Signal::JtagTms, 3, true),
+    PinAssignment::new(Signal::JtagEn, 5, true),
+    PinAssignment::new(Signal::JtagRstN, 7, true),
+];
+
+/// Signal layout of a single FT4232H channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PinMap {
+    lines: [Option<PinAssignment>; LINE_COUNT],
+}
+
+impl PinMap {
+    /// Create an empty pin map
+    pub const fn empty() -> Self {
+        Self {
+            lines: [None; LINE_COUNT],
+        }
+    }
+
+    /// Build a pin map from a list of assignments, validating each of them
+    pub fn new(assignments: &[PinAssignment]) -> Result<Self, Error> {
+        let mut map = Self::empty();
+        for assignment in assignments {
+            map.assign(*assignment)?;
+        }
+        Ok(map)
+    }
+
+    /// FACET2 channel A layout (eMMC SPI)
+    pub fn facet2_spi() -> Self {
+        Self::from_trusted(&FACET2_SPI)
+    }
+
+    /// FACET2 channel C layout (I2C)
+    pub fn facet2_i2c() -> Self {
+        Self::from_trusted(&FACET2_I2C)
+    }
+
+    /// FACET2 channel B layout (SB JTAG)
+    pub fn facet2_jtag() -> Self {
+        Self::from_trusted(&FACET2_JTAG)
+    }
+
+    /// Build a map from the built-in tables, which are validated by the unit tests
+    fn from_trusted(assignments: &[PinAssignment]) -> Self {
+        let mut map = Self::empty();
+        for assignment in assignments {
+            map.lines[assignment.line as usize] = Some(*assignment);
+        }
+        map
+    }
+
+    /// Add an assignment to the map
+    ///
+    /// Fails if the line or the signal is already taken, if the line number is
+    /// out of range or hardwired to another MPSSE signal, or if the direction
+    /// does not match what the signal requires.
+    pub fn assign(&mut self, assignment: PinAssignment) -> Result<(), Error> {
+        let line = assignment.line;
+        if line as usize >= LINE_COUNT {
+            return Err(ErrorKind::InvalidPinAssignment { line }.into());
+        }
+
+        if let Some(mpsse_line) = assignment.signal.mpsse_line()
+            && mpsse_line != line
+        {
+            return Err(ErrorKind::InvalidPinAssignment { line }.into());
+        }
+
+        if assignment.direction != assignment.signal.direction() {
+            return Err(ErrorKind::InvalidPinDirection { line }.into());
+        }
+
+        if self.lines[line as usize].is_some() {
+            return Err(ErrorKind::PinConflict { line }.into());
+        }
+
+        if let Some(existing) = self.assignment(assignment.signal) {
+            return Err(ErrorKind::PinConflict {
+                line: existing.line,
+            }
+            .into());
+        }
+
+        self.lines[line as usize] = Some(assignment);
+        Ok(())
+    }
+
+    /// Look up the assignment of a signal
+    pub fn assignment(&self, signal: Signal) -> Option<PinAssignment> {
+        self.iter().find(|a| a.signal == signal)
+    }
+
+    /// Single-bit mask of a signal, if it is assigned
+    pub fn mask(&self, signal: Signal) -> Option<u8> {
+        self.assignment(signal).map(|a| a.mask())
+    }
+
+    /// Single-bit mask of a signal that has to be present
+    pub fn require(&self, signal: Signal) -> Result<u8, Error> {
+        self.mask(signal).ok_or(ErrorKind::MissingPin(signal).into())
+    }
+
+    /// Mask of all lines configured as push-pull outputs
+    pub fn output_mask(&self) -> u8 {
+        self.iter()
+            .filter(|a| a.direction == Direction::Output)
+            .fold(0, |mask, a| mask | a.mask())
+    }
+
+    /// Pin state for all lines while idle
+    ///
+    /// Open-drain lines that idle high are reported as released (high).
+    pub fn idle_state(&self) -> u8 {
+        self.iter()
+            .filter(|a| a.idle_high)
+            .fold(0, |state, a| state | a.mask())
+    }
+
+    /// Iterate over all assigned lines
+    pub fn iter(&self) -> impl Iterator<Item = PinAssignment> + '_ {
+        self.lines.iter().flatten().copied()
+    }
+
+    /// Render the map as OpenOCD `ftdi layout_*` commands
+    pub fn openocd_layout(&self) -> OpenOcdLayout<'_> {
+        OpenOcdLayout(self)
+    }
+}
+
+/// OpenOCD `ftdi` driver layout for a [`PinMap`], see [`PinMap::openocd_layout`]
+pub struct OpenOcdLayout<'a>(&'a PinMap);
+
+impl fmt::Display for OpenOcdLayout<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let map = self.0;
+        writeln!(
+            f,
+            "ftdi layout_init {:#06X} {:#06X}",
+            map.idle_state(),
+            map.output_mask()
+        )?;
+
+        if let (Some(rst), Some(en)) = (map.mask(Signal::JtagRstN), map.mask(Signal::JtagEn)) {
+            writeln!(f, "ftdi layout_signal nTRST -data {rst:#06X} -oe {en:#06X}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_facet2_maps_are_valid() {
+        assert_eq!(PinMap::new(&FACET2_SPI).unwrap(), PinMap::facet2_spi());
+        assert_eq!(PinMap::new(&FACET2_I2C).unwrap(), PinMap::facet2_i2c());
+        assert_eq!(PinMap::new(&FACET2_JTAG).unwrap(), PinMap::facet2_jtag());
+    }
+
+    #[test]
+    fn test_facet2_spi_layout() {
+        let map = PinMap::facet2_spi();
+        assert_eq!(map.output_mask(), 0xAB);
+        assert_eq!(map.idle_state(), 0xA8);
+        assert_eq!(map.mask(Signal::SpiSsN), Some(0x08));
+        assert_eq!(map.mask(Signal::I2cScl), None);
+    }
+
+    #[test]
+    fn test_facet2_jtag_matches_openocd_cfg() {
+        let map = PinMap::facet2_jtag();
+        assert_eq!(map.idle_state(), 0xA8);
+        assert_eq!(map.output_mask(), 0xAB);
+        assert_eq!(
+            format!("{}", map.openocd_layout()),
+            "ftdi layout_init 0x00A8 0x00AB\nftdi layout_signal nTRST -data 0x0080 -oe 0x0020\n"
+        );
+    }
+
+    #[test]
+    fn test_facet2_i2c_layout() {
+        let map = PinMap::facet2_i2c();
+        assert_eq!(map.output_mask(), 0x00);
+        assert_eq!(map.idle_state(), 0xC0);
+        assert_eq!(map.require(Signal::I2cSda).unwrap(), 0x80);
+    }
+
+    #[test]
+    fn test_line_conflict() {
+        let mut map = PinMap::facet2_i2c();
+        let err = map
+            .assign(PinAssignment::new(Signal::SpiSsN, 7, true))
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::PinConflict { line: 7 }));
+    }
+
+    #[test]
+    fn test_signal_conflict() {
+        let mut map = PinMap::facet2_spi();
+        let err = map
+            .assign(PinAssignment::new(Signal::SpiSsN, 4, true))
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::PinConflict { line: 3 }));
+    }
+
+    #[test]
+    fn test_invalid_direction() {
+        let mut map = PinMap::empty();
+        let assignment = PinAssignment {
+            direction: Direction::Output,
+            ..PinAssignment::new(Signal::SpiMiso, 2, false)
+        };
+        assert!(matches!(
+            map.assign(assignment).map_err(Error::into_kind),
+            Err(ErrorKind::InvalidPinDirection { line: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_mpsse_lines_are_fixed() {
+        let mut map = PinMap::empty();
+        assert!(matches!(
+            map.assign(PinAssignment::new(Signal::SpiClk, 4, false)).map_err(Error::into_kind),
+            Err(ErrorKind::InvalidPinAssignment { line: 4 })
+        ));
+        assert!(matches!(
+            map.assign(PinAssignment::new(Signal::SpiSsN, 8, true)).map_err(Error::into_kind),
+            Err(ErrorKind::InvalidPinAssignment { line: 8 })
+        ));
+    }
+
+    #[test]
+    fn test_missing_pin() {
+        let map = PinMap::facet2_i2c();
+        assert!(matches!(
+            map.require(Signal::SpiSsN).map_err(Error::into_kind),
+            Err(ErrorKind::MissingPin(Signal::SpiSsN))
+        ));
+    }
+}
@@ -0,0 +1,487 @@
+//! GPIO bit-banged SPI backend
+//!
+//! This backend drives the eMMC SPI lines directly through
+//! `embedded_hal::digital` pins, for targets whose SPI peripheral cannot
+//! clock the 10-bit LSB-first header. It clocks the same bitstream as
+//! [`FtdiBackend`](super::ftdi) in SPI mode 0: data is shifted out on the
+//! falling edge (`LsbNeg`) and MISO is sampled on the rising edge (`LsbPos`).
+
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{InputPin, OutputPin},
+};
+
+use crate::prelude::*;
+use super::{GpioControl, HEADER_BITS, SpiBackend, SpiCapabilities, SpiSettings, header};
+use crate::error::{Error, SourceError};
+use crate::spi::protocol::commands::{Command, Register};
+
+/// Initial SPI clock
+const DEFAULT_CLOCK_HZ: u32 = 100_000;
+
+/// Fastest clock the half-period delay can express
+///
+/// The actual rate is further limited by how fast the pins toggle.
+const MAX_CLOCK_HZ: u32 = 5_000_000;
+
+/// Turnaround clocks between read header and response, same as FTDI
+const DEFAULT_TURNAROUND_CYCLES: u16 = 16;
+
+/// Bit-banged SPI Backend
+///
+/// * `CLK`  – Clock output (idle low)
+/// * `MOSI` – Data output
+/// * `MISO` – Data input
+/// * `CS`   – Chip select output (active low)
+/// * `RST`  – Optional reset pin (active low)
+/// * `EN`   – Optional enable pin (active low)
+/// * `D`    – Delay provider
+pub struct BitbangSpiBackend<CLK, MOSI, MISO, CS, RST, EN, D> {
+    clk: CLK,
+    mosi: MOSI,
+    miso: MISO,
+    cs: CS,
+    reset: Option<RST>,
+    enable: Option<EN>,
+    delay: D,
+    settings: SpiSettings,
+}
+
+impl<CLK, MOSI, MISO, CS, RST, EN, D> BitbangSpiBackend<CLK, MOSI, MISO, CS, RST, EN, D>
+where
+    CLK: OutputPin<Error: SourceError>,
+    MOSI: OutputPin<Error: SourceError>,
+    MISO: InputPin<Error: SourceError>,
+    CS: OutputPin<Error: SourceError>,
+    RST: OutputPin<Error: SourceError>,
+    EN: OutputPin<Error: SourceError>,
+    D: DelayNs,
+{
+    /// Create a new bit-banged SPI backend
+    pub fn new(
+        clk: CLK,
+        mosi: MOSI,
+        miso: MISO,
+        cs: CS,
+        reset: Option<RST>,
+        enable: Option<EN>,
+        delay: D,
+    ) -> Self {
+        Self {
+            clk,
+            mosi,
+            miso,
+            cs,
+            reset,
+            enable,
+            delay,
+            settings: SpiSettings {
+                clock_hz: DEFAULT_CLOCK_HZ,
+                turnaround_cycles: DEFAULT_TURNAROUND_CYCLES,
+            },
+        }
+    }
+
+    /// Release the pins and delay provider
+    pub fn release(self) -> (CLK, MOSI, MISO, CS, Option<RST>, Option<EN>, D) {
+        (
+            self.clk,
+            self.mosi,
+            self.miso,
+            self.cs,
+            self.reset,
+            self.enable,
+            self.delay,
+        )
+    }
+
+    fn half_period_ns(&self) -> u32 {
+        500_000_000 / self.settings.clock_hz
+    }
+
+    /// Clock one bit: drive MOSI while CLK is low, sample MISO on the rising edge
+    fn clock_bit(&mut self, out: bool) -> Result<bool, Error> {
+        let half_period = self.half_period_ns();
+
+        self.mosi
+            .set_state(out.into())
+            .map_err(Error::gpio)?;
+        self.delay.delay_ns(half_period);
+
+        self.clk.set_high().map_err(Error::gpio)?;
+        let sample = self.miso.is_high().map_err(Error::gpio)?;
+        self.delay.delay_ns(half_period);
+
+        self.clk.set_low().map_err(Error::gpio)?;
+
+        Ok(sample)
+    }
+
+    /// Clock out the lowest `count` bits of `value`, LSB-first
+    fn write_bits(&mut self, value: u32, count: usize) -> Result<(), Error> {
+        for bit in 0..count {
+            self.clock_bit(value >> bit & 1 != 0)?;
+        }
+        Ok(())
+    }
+
+    /// Clock in a byte, LSB-first
+    fn read_byte(&mut self) -> Result<u8, Error> {
+        let mut byte = 0;
+        for bit in 0..8 {
+            if self.clock_bit(false)? {
+                byte |= 1 << bit;
+            }
+        }
+        Ok(byte)
+    }
+
+    /// Run `f` with chip select asserted, releasing it even if `f` fails
+    fn with_chip_select<R>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<R, Error>,
+    ) -> Result<R, Error> {
+        self.set_chip_select(true)?;
+        let result = f(self);
+        self.set_chip_select(false)?;
+        result
+    }
+
+    /// Control reset pin (active low)
+    fn set_reset_internal(&mut self, asserted: bool) -> Result<(), Error> {
+        if let Some(pin) = self.reset.as_mut() {
+            pin.set_state((!asserted).into())
+                .map_err(Error::gpio)?;
+        }
+        Ok(())
+    }
+
+    /// Control enable pin (active low)
+    fn set_enable_internal(&mut self, enabled: bool) -> Result<(), Error> {
+        if let Some(pin) = self.enable.as_mut() {
+            pin.set_state((!enabled).into())
+                .map_err(Error::gpio)?;
+        }
+        Ok(())
+    }
+}
+
+impl<CLK, MOSI, MISO, CS, RST, EN, D> GpioControl
+    for BitbangSpiBackend<CLK, MOSI, MISO, CS, RST, EN, D>
+where
+    CLK: OutputPin<Error: SourceError>,
+    MOSI: OutputPin<Error: SourceError>,
+    MISO: InputPin<Error: SourceError>,
+    CS: OutputPin<Error: SourceError>,
+    RST: OutputPin<Error: SourceError>,
+    EN: OutputPin<Error: SourceError>,
+    D: DelayNs,
+{
+    fn set_chip_select(&mut self, asserted: bool) -> Result<(), Error> {
+        self.cs
+            .set_state((!asserted).into())
+            .map_err(Error::gpio)
+    }
+
+    fn set_reset(&mut self, asserted: bool) -> Result<(), Error> {
+        self.set_reset_internal(asserted)
+    }
+
+    fn set_enable(&mut self, enabled: bool) -> Result<(), Error> {
+        self.set_enable_internal(enabled)
+    }
+}
+
+impl<CLK, MOSI, MISO, CS, RST, EN, D> SpiBackend
+    for BitbangSpiBackend<CLK, MOSI, MISO, CS, RST, EN, D>
+where
+    CLK: OutputPin<Error: SourceError>,
+    MOSI: OutputPin<Error: SourceError>,
+    MISO: InputPin<Error: SourceError>,
+    CS: OutputPin<Error: SourceError>,
+    RST: OutputPin<Error: SourceError>,
+    EN: OutputPin<Error: SourceError>,
+    D: DelayNs,
+{
+    fn write_register<T: Into<u8>>(&mut self, register: T, data: u32) -> Result<(), Error> {
+        let header = header(Command::Write, register.into());
+
+        self.with_chip_select(|this| {
+            this.write_bits(header.into(), HEADER_BITS)?;
+            this.write_bits(data, 32)
+        })
+    }
+
+    fn read_register<T: Into<u8>>(&mut self, register: T) -> Result<u32, Error> {
+        let mut rx = [0u8; 4];
+        self.read_data(register, &mut rx)?;
+
+        Ok(u32::from_le_bytes(rx))
+    }
+
+    fn read_data<T: Into<u8>>(&mut self, register: T, buffer: &mut [u8]) -> Result<(), Error> {
+        let header = header(Command::Read, register.into());
+        let turnaround = self.settings.turnaround_cycles;
+
+        self.with_chip_select(|this| {
+            this.write_bits(header.into(), HEADER_BITS)?;
+
+            // Turnaround (wait time for device to prepare response)
+            for _ in 0..turnaround {
+                this.clock_bit(false)?;
+            }
+
+            for byte in buffer.iter_mut() {
+                *byte = this.read_byte()?;
+            }
+            Ok(())
+        })
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        self.set_reset_internal(true)?;
+        self.delay.delay_ns(100_000_000); // 100 ms
+        self.set_reset_internal(false)?;
+        Ok(())
+    }
+
+    fn initialize(&mut self) -> Result<(), Error> {
+        // Idle bus: CS released, clock low (mode 0)
+        self.clk.set_low().map_err(Error::gpio)?;
+        self.set_chip_select(false)?;
+        self.mosi.set_low().map_err(Error::gpio)?;
+
+        // Default pin states
+        self.set_enable_internal(true)?;
+        self.set_reset_internal(false)?;
+
+        // Perform reset sequence
+        self.reset()?;
+
+        Ok(())
+    }
+
+    fn capabilities(&self) -> SpiCapabilities {
+        SpiCapabilities {
+            min_clock_hz: 1,
+            max_clock_hz: MAX_CLOCK_HZ,
+            min_turnaround_cycles: 0,
+            max_turnaround_cycles: u16::MAX,
+        }
+    }
+
+    fn settings(&self) -> SpiSettings {
+        self.settings
+    }
+
+    fn apply_settings(&mut self, settings: SpiSettings) -> Result<(), Error> {
+        self.capabilities().validate(&settings)?;
+        self.settings = settings;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use crate::fixtures::NoDelay;
+    use core::cell::RefCell;
+    use core::convert::Infallible;
+    use embedded_hal::digital::ErrorType;
+    use std::rc::Rc;
+
+    /// Simulated eMMC SPI slave watching the bus lines
+    #[derive(Default)]
+    struct Bus {
+        clk: bool,
+        mosi: bool,
+        cs_n: bool,
+        rst_n: bool,
+        /// MOSI bits sampled on rising edges while selected
+        received: Vec<bool>,
+        /// Response bits, driven starting at `response_start`
+        response: Vec<u8>,
+        response_start: usize,
+        selects: usize,
+    }
+
+    impl Bus {
+        fn miso(&self) -> bool {
+            // Sampled after the rising edge of bit `received.len() - 1`
+            self.received
+                .len()
+                .checked_sub(1 + self.response_start)
+                .and_then(|i| {
+                    self.response
+                        .get(i / 8)
+                        .map(|byte| byte >> (i % 8) & 1 != 0)
+                })
+                .unwrap_or(true)
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    enum Line {
+        Clk,
+        Mosi,
+        CsN,
+        RstN,
+    }
+
+    struct Pin(Rc<RefCell<Bus>>, Line);
+
+    impl ErrorType for Pin {
+        type Error = Infallible;
+    }
+
+    impl Pin {
+        fn set(&mut self, level: bool) {
+            let mut bus = self.0.borrow_mut();
+            match self.1 {
+                Line::Clk => {
+                    if level && !bus.clk && !bus.cs_n {
+                        let mosi = bus.mosi;
+                        bus.received.push(mosi);
+                    }
+                    bus.clk = level;
+                }
+                Line::Mosi => {
+                    // Data may only change while the clock is low
+                    assert!(!bus.clk, "MOSI changed while CLK high");
+                    bus.mosi = level;
+                }
+                Line::CsN => {
+                    assert!(!bus.clk, "CS toggled while CLK high");
+                    if !level && bus.cs_n {
+                        bus.selects += 1;
+                        bus.received.clear();
+                    }
+                    bus.cs_n = level;
+                }
+                Line::RstN => bus.rst_n = level,
+            }
+        }
+    }
+
+    impl OutputPin for Pin {
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.set(false);
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.set(true);
+            Ok(())
+        }
+    }
+
+    struct Miso(Rc<RefCell<Bus>>);
+
+    impl ErrorType for Miso {
+        type Error = Infallible;
+    }
+
+    impl InputPin for Miso {
+        fn is_high(&mut self) -> Result<bool, Infallible> {
+            let bus = self.0.borrow();
+            assert!(bus.clk, "MISO sampled while CLK low");
+            Ok(bus.miso())
+        }
+
+        fn is_low(&mut self) -> Result<bool, Infallible> {
+            self.is_high().map(|high| !high)
+        }
+    }
+
+    type TestBackend = BitbangSpiBackend<Pin, Pin, Miso, Pin, Pin, Pin, NoDelay>;
+
+    fn backend(bus: &Rc<RefCell<Bus>>) -> TestBackend {
+        let mut backend = BitbangSpiBackend::new(
+            Pin(bus.clone(), Line::Clk),
+            Pin(bus.clone(), Line::Mosi),
+            Miso(bus.clone()),
+            Pin(bus.clone(), Line::CsN),
+            Some(Pin(bus.clone(), Line::RstN)),
+            None,
+            NoDelay,
+        );
+        backend.initialize().unwrap();
+        backend
+    }
+
+    fn bits(value: u32, count: usize) -> impl Iterator<Item = bool> {
+        (0..count).map(move |i| value >> i & 1 != 0)
+    }
+
+    #[test]
+    fn test_initialize() {
+        let bus = Rc::new(RefCell::new(Bus::default()));
+        let _backend = backend(&bus);
+
+        let bus = bus.borrow();
+        assert!(bus.cs_n);
+        assert!(!bus.clk);
+        assert!(bus.rst_n);
+    }
+
+    #[test]
+    fn test_write_register() {
+        let bus = Rc::new(RefCell::new(Bus::default()));
+        let mut backend = backend(&bus);
+
+        backend
+            .write_register(Register::Argument, 0xDEADBEEF)
+            .unwrap();
+
+        let expected: Vec<bool> = bits(Command::Write.bits().into(), 2)
+            .chain(bits(Register::Argument.address().into(), 8))
+            .chain(bits(0xDEADBEEF, 32))
+            .collect();
+
+        let bus = bus.borrow();
+        assert_eq!(bus.selects, 1);
+        assert_eq!(bus.received, expected);
+        assert!(bus.cs_n);
+    }
+
+    #[test]
+    fn test_read_register() {
+        for turnaround in [0, 5, 16] {
+            let bus = Rc::new(RefCell::new(Bus::default()));
+            let mut backend = backend(&bus);
+            backend.set_turnaround_cycles(turnaround).unwrap();
+
+            {
+                let mut bus = bus.borrow_mut();
+                bus.response = 0x1234_5678u32.to_le_bytes().to_vec();
+                bus.response_start = HEADER_BITS + usize::from(turnaround);
+            }
+
+            let value = backend.read_register(Register::InterruptStatus).unwrap();
+            assert_eq!(value, 0x1234_5678);
+
+            let header: Vec<bool> = bits(Command::Read.bits().into(), 2)
+                .chain(bits(Register::InterruptStatus.address().into(), 8))
+                .collect();
+
+            let bus = bus.borrow();
+            assert_eq!(bus.selects, 1);
+            assert_eq!(bus.received.len(), 10 + usize::from(turnaround) + 32);
+            assert_eq!(bus.received[..10], header[..]);
+        }
+    }
+
+    #[test]
+    fn test_clock_limits() {
+        let bus = Rc::new(RefCell::new(Bus::default()));
+        let mut backend = backend(&bus);
+
+        backend.set_clock_hz(MAX_CLOCK_HZ).unwrap();
+        assert_eq!(backend.half_period_ns(), 100);
+        assert!(matches!(
+            backend.set_clock_hz(0).map_err(Error::into_kind),
+            Err(ErrorKind::UnsupportedClock { clock_hz: 0 })
+        ));
+    }
+}
@@ -2,14 +2,62 @@
 //!
 //! This backend uses `embedded_hal::spi::SpiDevice` (eh1)
 //! with optional GPIO pins for reset and enable control.
+//!
+//! The wire format matches [`FtdiBackend`](super::ftdi): a 10-bit header
+//! (2-bit command, 8-bit register) and the data are clocked LSB-first in
+//! SPI mode 0. Since `SpiDevice` only moves whole bytes, the header and
+//! turnaround are bit-packed into the first bytes of the transaction and
+//! the response is shifted back into place afterwards. Unused trailing
+//! clocks are padded so that every transfer ends on a byte boundary.
 
-use embedded_hal::{delay::DelayNs, digital::OutputPin, spi::SpiDevice};
+use embedded_hal::{
+    delay::DelayNs,
+    digital::OutputPin,
+    spi::{Operation, SpiDevice},
+};
 
 use crate::prelude::*;
-use super::{GpioControl, SpiBackend};
-use crate::error::Error;
+use super::{GpioControl, HEADER_BITS, SpiBackend, SpiCapabilities, SpiSettings, header};
+use crate::error::{Error, ErrorKind, SourceError};
 use crate::spi::protocol::commands::{Command, Register};
 
+/// Nominal bus clock assumed when none is given
+///
+/// The clock itself is configured on the `SpiDevice`.
+const DEFAULT_CLOCK_HZ: u32 = 1_000_000;
+
+/// Turnaround clocks between read header and response, same as FTDI
+const DEFAULT_TURNAROUND_CYCLES: u16 = 16;
+
+/// Bytes reserved for header, turnaround and the first response bits
+const HEAD_LEN: usize = 8;
+
+/// Longest turnaround that fits into the head bytes
+const MAX_TURNAROUND_CYCLES: u16 = (HEAD_LEN * 8 - HEADER_BITS) as u16;
+
+/// Bit order the `SpiDevice` shifts bytes out with
+///
+/// Most controllers only support (or default to) MSB-first, in which case
+/// the backend mirrors every byte so the bits still leave LSB-first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BitOrder {
+    #[default]
+    MsbFirst,
+    LsbFirst,
+}
+
+impl BitOrder {
+    /// Convert between wire bytes and LSB-first bitstream bytes
+    fn convert(self, bytes: &mut [u8]) {
+        if self == BitOrder::MsbFirst {
+            for byte in bytes {
+                *byte = byte.reverse_bits();
+            }
+        }
+    }
+}
+
+
 /// embedded-hal 1.0 SPI Backend
 ///
 /// * `SPI`  – SPI device (chip-select handled by SpiDevice)
@@ -21,13 +69,16 @@ pub struct Eh1SpiBackend<SPI, RST, EN, D> {
     reset: Option<RST>,
     enable: Option<EN>,
     delay: D,
+    settings: SpiSettings,
+    bit_order: BitOrder,
+    settle_ns: u32,
 }
 
 impl<SPI, RST, EN, D> Eh1SpiBackend<SPI, RST, EN, D>
 where
-    SPI: SpiDevice,
-    RST: OutputPin,
-    EN: OutputPin,
+    SPI: SpiDevice<Error: SourceError>,
+    RST: OutputPin<Error: SourceError>,
+    EN: OutputPin<Error: SourceError>,
     D: DelayNs,
 {
     /// Create a new eh1 SPI backend
@@ -37,16 +88,84 @@ where
             reset,
             enable,
             delay,
+            settings: SpiSettings {
+                clock_hz: DEFAULT_CLOCK_HZ,
+                turnaround_cycles: DEFAULT_TURNAROUND_CYCLES,
+            },
+            bit_order: BitOrder::default(),
+            settle_ns: 0,
+        }
+    }
+
+    /// Set the bus clock the `SpiDevice` is configured for
+    ///
+    /// Only reported through [`SpiBackend::settings`], the clock cannot be
+    /// changed through the backend afterwards.
+    pub fn with_clock_hz(mut self, clock_hz: u32) -> Result<Self, Error> {
+        if clock_hz == 0 {
+            return Err(ErrorKind::UnsupportedClock { clock_hz }.into());
         }
+        self.settings.clock_hz = clock_hz;
+        Ok(self)
+    }
+
+    /// Set the bit order the `SpiDevice` is configured for
+    pub fn with_bit_order(mut self, bit_order: BitOrder) -> Self {
+        self.bit_order = bit_order;
+        self
+    }
+
+    /// Additional delay after the turnaround clocks, before the response
+    pub fn with_settle_ns(mut self, settle_ns: u32) -> Self {
+        self.settle_ns = settle_ns;
+        self
+    }
+
+    /// Clock out a read header and receive `buffer.len()` bytes of response
+    ///
+    /// The head bytes carry the header and turnaround clocks. When those do
+    /// not end on a byte boundary, the last head byte already holds the first
+    /// response bits and the buffer is shifted by that amount.
+    fn read_frame(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        let start = HEADER_BITS + usize::from(self.settings.turnaround_cycles);
+        let head_len = start.div_ceil(8);
+        let early_bits = head_len * 8 - start;
+
+        let mut head_tx = [0u8; HEAD_LEN];
+        head_tx[..2].copy_from_slice(&header(Command::Read, register).to_le_bytes());
+        self.bit_order.convert(&mut head_tx[..head_len]);
+        let mut head_rx = [0u8; HEAD_LEN];
+
+        self.spi
+            .transaction(&mut [
+                Operation::Transfer(&mut head_rx[..head_len], &head_tx[..head_len]),
+                Operation::DelayNs(self.settle_ns),
+                Operation::Read(buffer),
+            ])
+            .map_err(Error::spi)?;
+
+        self.bit_order.convert(&mut head_rx[..head_len]);
+        self.bit_order.convert(buffer);
+
+        if early_bits > 0 {
+            let mut carry = head_rx[head_len - 1] >> (8 - early_bits);
+            for byte in buffer.iter_mut() {
+                let next = *byte >> (8 - early_bits);
+                *byte = (*byte << early_bits) | carry;
+                carry = next;
+            }
+        }
+
+        Ok(())
     }
 
     /// Control reset pin (active low)
     fn set_reset_internal(&mut self, asserted: bool) -> Result<(), Error> {
         if let Some(pin) = self.reset.as_mut() {
             if asserted {
-                pin.set_low().map_err(|_| Error::InvalidGpioState)?;
+                pin.set_low().map_err(Error::gpio)?;
             } else {
-                pin.set_high().map_err(|_| Error::InvalidGpioState)?;
+                pin.set_high().map_err(Error::gpio)?;
             }
         }
         Ok(())
@@ -56,9 +175,9 @@ where
     fn set_enable_internal(&mut self, enabled: bool) -> Result<(), Error> {
         if let Some(pin) = self.enable.as_mut() {
             if enabled {
-                pin.set_low().map_err(|_| Error::InvalidGpioState)?;
+                pin.set_low().map_err(Error::gpio)?;
             } else {
-                pin.set_high().map_err(|_| Error::InvalidGpioState)?;
+                pin.set_high().map_err(Error::gpio)?;
             }
         }
         Ok(())
@@ -67,9 +186,9 @@ where
 
 impl<SPI, RST, EN, D> GpioControl for Eh1SpiBackend<SPI, RST, EN, D>
 where
-    SPI: SpiDevice,
-    RST: OutputPin,
-    EN: OutputPin,
+    SPI: SpiDevice<Error: SourceError>,
+    RST: OutputPin<Error: SourceError>,
+    EN: OutputPin<Error: SourceError>,
     D: DelayNs,
 {
     fn set_chip_select(&mut self, _asserted: bool) -> Result<(), Error> {
@@ -88,47 +207,36 @@ where
 
 impl<SPI, RST, EN, D> SpiBackend for Eh1SpiBackend<SPI, RST, EN, D>
 where
-    SPI: SpiDevice,
-    RST: OutputPin,
-    EN: OutputPin,
+    SPI: SpiDevice<Error: SourceError>,
+    RST: OutputPin<Error: SourceError>,
+    EN: OutputPin<Error: SourceError>,
     D: DelayNs,
 {
     fn write_register<T: Into<u8>>(&mut self, register: T, data: u32) -> Result<(), Error> {
-        let mut frame = [0u8; 6];
+        // 10 header bits + 32 data bits, padded with 6 trailing clocks
+        let frame =
+            u64::from(header(Command::Write, register.into())) | (u64::from(data) << HEADER_BITS);
 
-        frame[0] = Command::Write.bits();
-        frame[1] = register.into();
-        frame[2..6].copy_from_slice(&data.to_le_bytes());
+        let mut tx = [0u8; 6];
+        tx.copy_from_slice(&frame.to_le_bytes()[..6]);
+        self.bit_order.convert(&mut tx);
 
-        self.spi.write(&frame).map_err(|_| Error::SpiError)?;
+        self.spi
+            .transaction(&mut [Operation::Write(&tx)])
+            .map_err(Error::spi)?;
 
         Ok(())
     }
 
     fn read_register<T: Into<u8>>(&mut self, register: T) -> Result<u32, Error> {
-        let tx = [Command::Read.bits(), register.into()];
         let mut rx = [0u8; 4];
-
-        self.spi.write(&tx).map_err(|_| Error::SpiError)?;
-
-        // Match FTDI dummy clock delay
-        self.delay.delay_ns(1_000);
-
-        self.spi.read(&mut rx).map_err(|_| Error::SpiError)?;
+        self.read_frame(register.into(), &mut rx)?;
 
         Ok(u32::from_le_bytes(rx))
     }
 
     fn read_data<T: Into<u8>>(&mut self, register: T, buffer: &mut [u8]) -> Result<(), Error> {
-        let tx = [Command::Read.bits(), register.into()];
-
-        self.spi.write(&tx).map_err(|_| Error::SpiError)?;
-
-        self.delay.delay_ns(1_000);
-
-        self.spi.read(buffer).map_err(|_| Error::SpiError)?;
-
-        Ok(())
+        self.read_frame(register.into(), buffer)
     }
 
     fn reset(&mut self) -> Result<(), Error> {
@@ -148,4 +256,237 @@ where
 
         Ok(())
     }
+
+    fn capabilities(&self) -> SpiCapabilities {
+        // The clock belongs to the SpiDevice and cannot be changed from here
+        SpiCapabilities {
+            min_clock_hz: self.settings.clock_hz,
+            max_clock_hz: self.settings.clock_hz,
+            min_turnaround_cycles: 0,
+            max_turnaround_cycles: MAX_TURNAROUND_CYCLES,
+        }
+    }
+
+    fn settings(&self) -> SpiSettings {
+        self.settings
+    }
+
+    /// Only the turnaround can be changed, the clock is set up on the `SpiDevice`
+    fn apply_settings(&mut self, settings: SpiSettings) -> Result<(), Error> {
+        if settings.clock_hz != self.settings.clock_hz {
+            return Err(ErrorKind::Unsupported {
+                operation: "Changing the clock of an SpiDevice",
+            }
+            .into());
+        }
+        self.capabilities().validate(&settings)?;
+        self.settings = settings;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{NoDelay, NoPin};
+    use core::convert::Infallible;
+    use embedded_hal::spi::ErrorType;
+
+    /// Bitstream the FTDI backend clocks for a transaction, one entry per clock
+    ///
+    /// Mirrors the MPSSE command sequence: `clock_bits_out` of 2 command bits
+    /// and 8 register bits, then either 32 data bits (write) or the turnaround
+    /// clocks followed by the response (read), all LSB-first.
+    fn ftdi_mosi(command: Command, register: u8, turnaround: u16, data: Option<u32>) -> Vec<bool> {
+        let mut bits = vec![];
+        bits.extend((0..2).map(|i| command.bits() >> i & 1 != 0));
+        bits.extend((0..8).map(|i| register >> i & 1 != 0));
+        match data {
+            Some(data) => bits.extend((0..32).map(|i| data >> i & 1 != 0)),
+            None => bits.extend((0..turnaround).map(|_| false)),
+        }
+        bits
+    }
+
+    /// Device model that answers a read at the bit position the FTDI timing implies
+    struct MockDevice {
+        bit_order: BitOrder,
+        turnaround: u16,
+        response: Vec<u8>,
+        mosi: Vec<bool>,
+        transactions: usize,
+    }
+
+    impl MockDevice {
+        fn new(bit_order: BitOrder, turnaround: u16, response: &[u8]) -> Self {
+            Self {
+                bit_order,
+                turnaround,
+                response: response.to_vec(),
+                mosi: vec![],
+                transactions: 0,
+            }
+        }
+
+        fn wire(&self, byte: u8) -> u8 {
+            match self.bit_order {
+                BitOrder::MsbFirst => byte.reverse_bits(),
+                BitOrder::LsbFirst => byte,
+            }
+        }
+
+        /// Clock one byte, returning the MISO byte
+        fn clock(&mut self, tx: u8) -> u8 {
+            let tx = self.wire(tx);
+            let start = HEADER_BITS + usize::from(self.turnaround);
+
+            let mut rx = 0;
+            for bit in 0..8 {
+                let position = self.mosi.len();
+                self.mosi.push(tx >> bit & 1 != 0);
+
+                let data_bit = position
+                    .checked_sub(start)
+                    .and_then(|i| self.response.get(i / 8).map(|byte| byte >> (i % 8) & 1));
+                rx |= data_bit.unwrap_or(1) << bit;
+            }
+            self.wire(rx)
+        }
+    }
+
+    impl ErrorType for MockDevice {
+        type Error = Infallible;
+    }
+
+    impl SpiDevice for MockDevice {
+        fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Infallible> {
+            // Chip select is asserted for the whole transaction
+            self.transactions += 1;
+            self.mosi.clear();
+
+            for op in operations {
+                match op {
+                    Operation::Write(tx) => {
+                        for &byte in tx.iter() {
+                            self.clock(byte);
+                        }
+                    }
+                    Operation::Read(rx) => {
+                        for byte in rx.iter_mut() {
+                            *byte = self.clock(0);
+                        }
+                    }
+                    Operation::Transfer(rx, tx) => {
+                        for (rx, &tx) in rx.iter_mut().zip(tx.iter()) {
+                            *rx = self.clock(tx);
+                        }
+                    }
+                    Operation::TransferInPlace(buf) => {
+                        for byte in buf.iter_mut() {
+                            *byte = self.clock(*byte);
+                        }
+                    }
+                    Operation::DelayNs(_) => {}
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn backend(device: MockDevice) -> Eh1SpiBackend<MockDevice, NoPin, NoPin, NoDelay> {
+        let (bit_order, turnaround) = (device.bit_order, device.turnaround);
+        let mut backend = Eh1SpiBackend::new(device, None, None, NoDelay).with_bit_order(bit_order);
+        backend.set_turnaround_cycles(turnaround).unwrap();
+        backend
+    }
+
+    #[test]
+    fn test_write_matches_ftdi() {
+        for bit_order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+            let mut backend = backend(MockDevice::new(bit_order, 16, &[]));
+            backend
+                .write_register(Register::Argument, 0xDEADBEEF)
+                .unwrap();
+
+            let expected = ftdi_mosi(
+                Command::Write,
+                Register::Argument.into(),
+                16,
+                Some(0xDEADBEEF),
+            );
+            let mosi = &backend.spi.mosi;
+            assert_eq!(backend.spi.transactions, 1);
+            assert_eq!(mosi.len(), 48);
+            assert_eq!(mosi[..42], expected[..]);
+            assert!(mosi[42..].iter().all(|bit| !bit));
+        }
+    }
+
+    #[test]
+    fn test_read_register_matches_ftdi() {
+        for bit_order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+            for turnaround in [0, 3, 6, 16, 21, MAX_TURNAROUND_CYCLES] {
+                let device = MockDevice::new(bit_order, turnaround, &0x1234_5678u32.to_le_bytes());
+                let mut backend = backend(device);
+
+                let value = backend.read_register(Register::InterruptStatus).unwrap();
+                assert_eq!(value, 0x1234_5678, "{bit_order:?}, {turnaround} cycles");
+
+                let expected = ftdi_mosi(
+                    Command::Read,
+                    Register::InterruptStatus.into(),
+                    turnaround,
+                    None,
+                );
+                let mosi = &backend.spi.mosi;
+                assert_eq!(backend.spi.transactions, 1);
+                assert_eq!(mosi[..expected.len()], expected[..]);
+                assert!(mosi.len() >= expected.len() + 32);
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_data_matches_ftdi() {
+        let page: Vec<u8> = (0..512).map(|i| (i * 7 + i / 3) as u8).collect();
+
+        for turnaround in [16, 17, 24] {
+            let device = MockDevice::new(BitOrder::MsbFirst, turnaround, &page);
+            let mut backend = backend(device);
+
+            let mut buffer = [0u8; 512];
+            backend.read_data(Register::DataFifo, &mut buffer).unwrap();
+            assert_eq!(buffer[..], page[..]);
+            assert_eq!(backend.spi.transactions, 1);
+        }
+    }
+
+    #[test]
+    fn test_turnaround_limit() {
+        let mut backend = backend(MockDevice::new(BitOrder::MsbFirst, 16, &[]));
+        assert!(matches!(
+            backend.set_turnaround_cycles(MAX_TURNAROUND_CYCLES + 1).map_err(Error::into_kind),
+            Err(ErrorKind::UnsupportedTurnaround { .. })
+        ));
+    }
+
+    #[test]
+    fn test_settings_validation() {
+        let mut backend = backend(MockDevice::new(BitOrder::MsbFirst, 16, &[]));
+        backend.set_turnaround_cycles(MAX_TURNAROUND_CYCLES).unwrap();
+        backend.set_clock_hz(DEFAULT_CLOCK_HZ).unwrap();
+        assert!(matches!(
+            backend.set_clock_hz(DEFAULT_CLOCK_HZ * 2).map_err(Error::into_kind),
+            Err(ErrorKind::Unsupported { .. })
+        ));
+        assert_eq!(backend.settings().clock_hz, DEFAULT_CLOCK_HZ);
+        assert_eq!(backend.settings().turnaround_cycles, MAX_TURNAROUND_CYCLES);
+
+        let backend = backend.with_clock_hz(400_000).unwrap();
+        assert_eq!(backend.capabilities().max_clock_hz, 400_000);
+        assert!(matches!(
+            backend.with_clock_hz(0).map_err(Error::into_kind),
+            Err(ErrorKind::UnsupportedClock { clock_hz: 0 })
+        ));
+    }
 }
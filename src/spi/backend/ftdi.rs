@@ -5,12 +5,13 @@ use libftd2xx::{Ft4232h, FtdiCommon, FtdiMpsse, MpsseCmdBuilder, MpsseCmdExecuto
 /// This backend provides direct FTDI MPSSE access for maximum performance.
 use std::time::Duration;
 
-use super::{GpioControl, SpiBackend};
-use crate::error::Error;
+use super::{GpioControl, SpiBackend, SpiCapabilities, SpiSettings};
+use crate::error::{Error, ErrorKind};
+use crate::pinmap::{PinMap, Signal};
 use crate::spi::protocol::commands::{Command, Register};
 
 /*
-Pin assignments on FTDI FT4232H:
+Pin assignments on FTDI FT4232H (FACET2, see `PinMap::facet2_spi`):
 SPI_CLK:   AD0
 SPI_MOSI:  AD1
 SPI_MISO:  AD2
@@ -33,40 +34,216 @@ bitflags! {
     }
 }
 
-/// FTDI SPI Backend
-pub struct FtdiBackend {
-    dev: Ft4232h,
+/// MPSSE clock limits (60 MHz base clock, optional divide-by-5, 16-bit divisor)
+const MIN_CLOCK_HZ: u32 = 92;
+const MAX_CLOCK_HZ: u32 = 30_000_000;
+
+/// Initial SPI clock
+///
+/// `FtdiMpsse::set_clock` takes Hz, so the link comes up at 149 Hz (not kHz)
+/// until it is raised with [`SpiBackend::set_clock_hz`].
+const DEFAULT_CLOCK_HZ: u32 = 149;
+
+/// Turnaround from the protocol trace: `0x8F 0x01 0x00` clocks (1 + 1) * 8 cycles
+const DEFAULT_TURNAROUND_CYCLES: u16 = 16;
+
+/// MPSSE opcodes: clock n x 8 / n bits without data transfer
+const MPSSE_CLK_BYTES_NO_DATA: u8 = 0x8F;
+const MPSSE_CLK_BITS_NO_DATA: u8 = 0x8E;
+
+/// Upper bound for a read command sequence (GPIO, header, turnaround, read, GPIO)
+const MAX_READ_CMD_LEN: usize = 32;
+
+/// Control lines resolved from a [`PinMap`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct SpiLines {
+    ss_n: SpiPin,
+    en_n: SpiPin,
+    rst_n: SpiPin,
+    /// Lines configured as outputs
+    directions: SpiPin,
+    /// Line state while idle
+    idle: SpiPin,
 }
 
-impl FtdiBackend {
-    /// Create a new FTDI backend with the specified device
-    pub fn new(dev: Ft4232h) -> Self {
-        Self { dev }
+impl SpiLines {
+    /// FACET2 layout
+    const FACET2: Self = Self {
+        ss_n: SpiPin::SS_N,
+        en_n: SpiPin::EN_N,
+        rst_n: SpiPin::RST_N,
+        directions: SpiPin::CLK
+            .union(SpiPin::MOSI)
+            .union(SpiPin::SS_N)
+            .union(SpiPin::EN_N)
+            .union(SpiPin::RST_N),
+        idle: SpiPin::SS_N.union(SpiPin::EN_N).union(SpiPin::RST_N),
+    };
+
+    fn from_pin_map(map: &PinMap) -> Result<Self, Error> {
+        for signal in [Signal::SpiClk, Signal::SpiMosi, Signal::SpiMiso] {
+            map.require(signal)?;
+        }
+
+        Ok(Self {
+            ss_n: SpiPin::from_bits_retain(map.require(Signal::SpiSsN)?),
+            en_n: SpiPin::from_bits_retain(map.require(Signal::SpiEnN)?),
+            rst_n: SpiPin::from_bits_retain(map.require(Signal::SpiRstN)?),
+            directions: SpiPin::from_bits_retain(map.output_mask()),
+            idle: SpiPin::from_bits_retain(map.idle_state()),
+        })
+    }
+}
+
+/// Channel access used by [`FtdiBackend`]
+///
+/// Implemented for the FT4232H, the backend never touches the channel in
+/// any other way.
+pub trait MpsseChannel {
+    /// Switch the channel to MPSSE mode
+    fn enable_mpsse(&mut self) -> Result<(), Error>;
+
+    fn set_clock(&mut self, clock_hz: u32) -> Result<(), Error>;
+
+    /// Current level of the lower GPIO byte
+    fn gpio_lower(&mut self) -> Result<u8, Error>;
+
+    fn set_gpio_lower(&mut self, state: u8, direction: u8) -> Result<(), Error>;
+
+    fn set_gpio_upper(&mut self, state: u8, direction: u8) -> Result<(), Error>;
+
+    /// Send a MPSSE command sequence
+    fn send(&mut self, cmd: &[u8]) -> Result<(), Error>;
+
+    /// Receive the data clocked in by previously sent commands
+    fn recv(&mut self, buf: &mut [u8]) -> Result<(), Error>;
+}
+
+impl MpsseChannel for Ft4232h {
+    fn enable_mpsse(&mut self) -> Result<(), Error> {
+        self.set_bit_mode(0x0, libftd2xx::BitMode::Mpsse)?;
+        self.set_latency_timer(Duration::from_millis(2))?;
+        self.set_usb_parameters(64)?;
+        Ok(())
+    }
+
+    fn set_clock(&mut self, clock_hz: u32) -> Result<(), Error> {
+        FtdiMpsse::set_clock(self, clock_hz)?;
+        Ok(())
+    }
+
+    fn gpio_lower(&mut self) -> Result<u8, Error> {
+        Ok(FtdiMpsse::gpio_lower(self)?)
     }
 
+    fn set_gpio_lower(&mut self, state: u8, direction: u8) -> Result<(), Error> {
+        FtdiMpsse::set_gpio_lower(self, state, direction)?;
+        Ok(())
+    }
+
+    fn set_gpio_upper(&mut self, state: u8, direction: u8) -> Result<(), Error> {
+        FtdiMpsse::set_gpio_upper(self, state, direction)?;
+        Ok(())
+    }
+
+    fn send(&mut self, cmd: &[u8]) -> Result<(), Error> {
+        MpsseCmdExecutor::send(self, cmd)?;
+        Ok(())
+    }
+
+    fn recv(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        MpsseCmdExecutor::recv(self, buf)?;
+        Ok(())
+    }
+}
+
+/// FTDI SPI Backend
+///
+/// The backend is the only one driving the channel's GPIO lines, so it keeps
+/// track of their output state itself instead of reading it back before every
+/// transaction. Call [`FtdiBackend::resync_gpio`] if something else changed them.
+pub struct FtdiBackend<D = Ft4232h> {
+    dev: D,
+    lines: SpiLines,
+    /// Last GPIO output state written to the device
+    gpio_state: SpiPin,
+    settings: SpiSettings,
+    /// Whether the channel is in MPSSE mode, so clock changes can be sent
+    mpsse_enabled: bool,
+}
+
+impl FtdiBackend {
     /// Open FTDI device by description
     pub fn open(description: &str) -> Result<Self, Error> {
         let dev = Ft4232h::with_description(description)?;
         Ok(Self::new(dev))
     }
+}
+
+impl<D: MpsseChannel> FtdiBackend<D> {
+    const DEFAULT_SETTINGS: SpiSettings = SpiSettings {
+        clock_hz: DEFAULT_CLOCK_HZ,
+        turnaround_cycles: DEFAULT_TURNAROUND_CYCLES,
+    };
+
+    /// Create a new FTDI backend with the specified device, using the FACET2 pin layout
+    pub fn new(dev: D) -> Self {
+        Self {
+            dev,
+            lines: SpiLines::FACET2,
+            gpio_state: SpiLines::FACET2.idle,
+            settings: Self::DEFAULT_SETTINGS,
+            mpsse_enabled: false,
+        }
+    }
+
+    /// Create a new FTDI backend with a custom pin layout
+    ///
+    /// The map has to provide the SPI clock/data lines as well as
+    /// `SpiSsN`, `SpiEnN` and `SpiRstN`.
+    pub fn with_pin_map(dev: D, pin_map: PinMap) -> Result<Self, Error> {
+        let lines = SpiLines::from_pin_map(&pin_map)?;
+        Ok(Self {
+            dev,
+            lines,
+            gpio_state: lines.idle,
+            settings: Self::DEFAULT_SETTINGS,
+            mpsse_enabled: false,
+        })
+    }
 
     /// Get pin direction configuration (which pins are outputs)
-    fn pin_directions() -> SpiPin {
-        SpiPin::CLK | SpiPin::MOSI | SpiPin::SS_N | SpiPin::EN_N | SpiPin::RST_N
+    fn pin_directions(&self) -> SpiPin {
+        self.lines.directions
     }
 
-    /// Read current GPIO state
+    /// Read current GPIO state from the device
     fn get_data_bits(&mut self) -> Result<SpiPin, Error> {
         let bits = self.dev.gpio_lower()?;
-        SpiPin::from_bits(bits).ok_or(Error::InvalidGpioState)
+        SpiPin::from_bits(bits).ok_or(ErrorKind::InvalidGpioState.into())
+    }
+
+    /// Re-read the GPIO state from the device and update the cached output state
+    ///
+    /// Only needed if something outside of this backend changed the pins.
+    pub fn resync_gpio(&mut self) -> Result<SpiPin, Error> {
+        let bits = self.get_data_bits()?;
+        self.gpio_state = bits & self.pin_directions();
+        Ok(self.gpio_state)
+    }
+
+    /// Cached GPIO output state
+    pub fn gpio_state(&self) -> SpiPin {
+        self.gpio_state
     }
 
     /// Set GPIO pins to specific absolute state
     fn set_data_bits_absolute(&mut self, state: SpiPin) -> Result<(), Error> {
         self.dev
-            .set_gpio_lower(state.bits(), Self::pin_directions().bits())?;
+            .set_gpio_lower(state.bits(), self.pin_directions().bits())?;
         self.dev
             .set_gpio_upper(SpiPin::empty().bits(), SpiPin::empty().bits())?;
+        self.gpio_state = state;
         Ok(())
     }
 
@@ -77,7 +254,7 @@ impl FtdiBackend {
         high: bool,
     ) -> Result<SpiPin, Error> {
         if target_bits.bits().count_ones() != 1 {
-            return Err(Error::InvalidPinMask);
+            return Err(ErrorKind::InvalidPinMask.into());
         }
 
         let bits_set = if high {
@@ -89,40 +266,72 @@ impl FtdiBackend {
         Ok(bits_set)
     }
 
+    /// MPSSE commands clocking `cycles` turnaround cycles without data transfer
+    fn turnaround_command(cycles: u16) -> ([u8; 5], usize) {
+        let mut cmd = [0u8; 5];
+        let mut len = 0;
+
+        let bytes = cycles / 8;
+        if bytes > 0 {
+            let [lo, hi] = (bytes - 1).to_le_bytes();
+            cmd[..3].copy_from_slice(&[MPSSE_CLK_BYTES_NO_DATA, lo, hi]);
+            len += 3;
+        }
+
+        let bits = (cycles % 8) as u8;
+        if bits > 0 {
+            cmd[len..len + 2].copy_from_slice(&[MPSSE_CLK_BITS_NO_DATA, bits - 1]);
+            len += 2;
+        }
+
+        (cmd, len)
+    }
+
+    /// Concatenate MPSSE command sequences into one buffer for a single USB transfer
+    fn join_commands(parts: &[&[u8]]) -> ([u8; MAX_READ_CMD_LEN], usize) {
+        let mut cmd = [0u8; MAX_READ_CMD_LEN];
+        let mut len = 0;
+        for part in parts {
+            cmd[len..len + part.len()].copy_from_slice(part);
+            len += part.len();
+        }
+        (cmd, len)
+    }
+
     /// Set a single pin high or low
     pub fn set_single_pin(&mut self, target_pin: SpiPin, high: bool) -> Result<(), Error> {
-        let current = self.get_data_bits()?;
-        let updated = Self::set_data_bits_single(current, target_pin, high)?;
+        let updated = Self::set_data_bits_single(self.gpio_state, target_pin, high)?;
         self.dev
-            .set_gpio_lower(updated.bits(), Self::pin_directions().bits())?;
+            .set_gpio_lower(updated.bits(), self.pin_directions().bits())?;
+        self.gpio_state = updated;
         Ok(())
     }
 }
 
-impl GpioControl for FtdiBackend {
+impl<D: MpsseChannel> GpioControl for FtdiBackend<D> {
     fn set_chip_select(&mut self, asserted: bool) -> Result<(), Error> {
         // SS_N is active low, so asserted=true means pin=low
-        self.set_single_pin(SpiPin::SS_N, !asserted)
+        self.set_single_pin(self.lines.ss_n, !asserted)
     }
 
     fn set_reset(&mut self, asserted: bool) -> Result<(), Error> {
         // RST_N is active low, so asserted=true means pin=low
-        self.set_single_pin(SpiPin::RST_N, !asserted)
+        self.set_single_pin(self.lines.rst_n, !asserted)
     }
 
     fn set_enable(&mut self, enabled: bool) -> Result<(), Error> {
         // EN_N is active low, so enabled=true means pin=low
-        self.set_single_pin(SpiPin::EN_N, !enabled)
+        self.set_single_pin(self.lines.en_n, !enabled)
     }
 }
 
-impl SpiBackend for FtdiBackend {
+impl<D: MpsseChannel> SpiBackend for FtdiBackend<D> {
     fn write_register<T: Into<u8>>(&mut self, register: T, data: u32) -> Result<(), Error> {
-        let bits = self.get_data_bits()?;
+        let bits = self.gpio_state;
 
         let builder = MpsseCmdBuilder::new()
             // Assert ChipSelect
-            .set_gpio_lower((bits & !SpiPin::SS_N).bits(), Self::pin_directions().bits())
+            .set_gpio_lower((bits & !self.lines.ss_n).bits(), self.pin_directions().bits())
             // Send command bits (2 bits: WRITE = 0x2)
             .clock_bits_out(
                 libftd2xx::ClockBitsOut::LsbNeg,
@@ -138,18 +347,18 @@ impl SpiBackend for FtdiBackend {
             // Send data (4 bytes, little-endian)
             .clock_data_out(libftd2xx::ClockDataOut::LsbNeg, &data.to_le_bytes())
             // Release ChipSelect
-            .set_gpio_lower((bits | SpiPin::SS_N).bits(), Self::pin_directions().bits());
+            .set_gpio_lower((bits | self.lines.ss_n).bits(), self.pin_directions().bits());
 
         self.dev.send(builder.as_slice())?;
         Ok(())
     }
 
     fn read_register<T: Into<u8>>(&mut self, register: T) -> Result<u32, Error> {
-        let bits = self.get_data_bits()?;
+        let bits = self.gpio_state;
 
         let builder = MpsseCmdBuilder::new()
             // Assert ChipSelect
-            .set_gpio_lower((bits & !SpiPin::SS_N).bits(), Self::pin_directions().bits())
+            .set_gpio_lower((bits & !self.lines.ss_n).bits(), self.pin_directions().bits())
             // Send command bits (2 bits: READ = 0x1)
             .clock_bits_out(
                 libftd2xx::ClockBitsOut::LsbNeg,
@@ -167,16 +376,20 @@ impl SpiBackend for FtdiBackend {
             // Read 4 bytes of data
             .clock_data_in(libftd2xx::ClockDataIn::LsbPos, 4)
             // Release ChipSelect
-            .set_gpio_lower((bits | SpiPin::SS_N).bits(), Self::pin_directions().bits())
+            .set_gpio_lower((bits | self.lines.ss_n).bits(), self.pin_directions().bits())
             .send_immediate();
 
-        let mut final_cmd = vec![];
-        final_cmd.extend_from_slice(builder.as_slice());
-        // Clock 8 cycles (wait time for device to prepare response)
-        final_cmd.extend_from_slice(&[0x8F, 0x01, 0x00]);
-        final_cmd.extend_from_slice(builder2.as_slice());
+        let (turnaround, turnaround_len) =
+            Self::turnaround_command(self.settings.turnaround_cycles);
 
-        self.dev.send(final_cmd.as_slice())?;
+        // Clock turnaround cycles (wait time for device to prepare response)
+        let (final_cmd, final_len) = Self::join_commands(&[
+            builder.as_slice(),
+            &turnaround[..turnaround_len],
+            builder2.as_slice(),
+        ]);
+
+        self.dev.send(&final_cmd[..final_len])?;
 
         let mut recv_buffer = [0u8; 4];
         self.dev.recv(&mut recv_buffer)?;
@@ -185,11 +398,11 @@ impl SpiBackend for FtdiBackend {
     }
 
     fn read_data<T: Into<u8>>(&mut self, register: T, buffer: &mut [u8]) -> Result<(), Error> {
-        let bits = self.get_data_bits()?;
+        let bits = self.gpio_state;
 
         let builder = MpsseCmdBuilder::new()
             // Assert ChipSelect
-            .set_gpio_lower((bits & !SpiPin::SS_N).bits(), Self::pin_directions().bits())
+            .set_gpio_lower((bits & !self.lines.ss_n).bits(), self.pin_directions().bits())
             // Send command bits (2 bits: READ = 0x1)
             .clock_bits_out(
                 libftd2xx::ClockBitsOut::LsbNeg,
@@ -207,16 +420,20 @@ impl SpiBackend for FtdiBackend {
             // Read 512 bytes of data
             .clock_data_in(libftd2xx::ClockDataIn::LsbPos, buffer.len())
             // Release ChipSelect
-            .set_gpio_lower((bits | SpiPin::SS_N).bits(), Self::pin_directions().bits())
+            .set_gpio_lower((bits | self.lines.ss_n).bits(), self.pin_directions().bits())
             .send_immediate();
 
-        let mut final_cmd = vec![];
-        final_cmd.extend_from_slice(builder.as_slice());
-        // Clock 8 cycles (wait time)
-        final_cmd.extend_from_slice(&[0x8F, 0x01, 0x00]);
-        final_cmd.extend_from_slice(builder2.as_slice());
+        let (turnaround, turnaround_len) =
+            Self::turnaround_command(self.settings.turnaround_cycles);
 
-        self.dev.send(final_cmd.as_slice())?;
+        // Clock turnaround cycles (wait time)
+        let (final_cmd, final_len) = Self::join_commands(&[
+            builder.as_slice(),
+            &turnaround[..turnaround_len],
+            builder2.as_slice(),
+        ]);
+
+        self.dev.send(&final_cmd[..final_len])?;
         self.dev.recv(buffer)?;
 
         Ok(())
@@ -236,16 +453,11 @@ impl SpiBackend for FtdiBackend {
     }
 
     fn initialize(&mut self) -> Result<(), Error> {
-        // Set MPSSE mode
-        self.dev.set_bit_mode(0x0, libftd2xx::BitMode::Mpsse)?;
-
-        // Set latency timer
-        self.dev.set_latency_timer(Duration::from_millis(2))?;
-
-        self.dev.set_usb_parameters(64)?;
+        // Set MPSSE mode, latency timer and USB transfer size
+        self.dev.enable_mpsse()?;
 
         // Set initial GPIO state: SS_N=HIGH, EN_N=HIGH, RST_N=HIGH
-        self.set_data_bits_absolute(SpiPin::SS_N | SpiPin::EN_N | SpiPin::RST_N)?;
+        self.set_data_bits_absolute(self.lines.idle)?;
 
         // Enable SPI level shifter (EN_N is active low)
         self.set_enable(true)?;
@@ -259,18 +471,95 @@ impl SpiBackend for FtdiBackend {
         // Release chip select
         self.set_chip_select(false)?;
 
-        // Setup clock frequency (149 kHz)
+        // Setup clock frequency
         // TODO: After frequency training, it should get increased automatically
-        self.dev.set_clock(149)?;
+        self.dev.set_clock(self.settings.clock_hz)?;
+        self.mpsse_enabled = true;
 
         Ok(())
     }
+
+    fn capabilities(&self) -> SpiCapabilities {
+        SpiCapabilities {
+            min_clock_hz: MIN_CLOCK_HZ,
+            max_clock_hz: MAX_CLOCK_HZ,
+            min_turnaround_cycles: 0,
+            max_turnaround_cycles: u16::MAX,
+        }
+    }
+
+    fn settings(&self) -> SpiSettings {
+        self.settings
+    }
+
+    fn apply_settings(&mut self, settings: SpiSettings) -> Result<(), Error> {
+        self.capabilities().validate(&settings)?;
+
+        // Before initialize() the clock is only stored and set up with MPSSE mode
+        if self.mpsse_enabled && settings.clock_hz != self.settings.clock_hz {
+            self.dev.set_clock(settings.clock_hz)?;
+        }
+
+        self.settings = settings;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Channel remembering its GPIO writes, with fixed input levels
+    #[derive(Default)]
+    struct MockChannel {
+        /// Level of all lower GPIO lines as read back
+        pins: u8,
+        /// (state, direction) of every lower GPIO write
+        gpio_writes: Vec<(u8, u8)>,
+        /// Number of lower GPIO reads
+        gpio_reads: usize,
+        /// Payload of every command stream sent
+        sent: Vec<Vec<u8>>,
+        clocks: Vec<u32>,
+    }
+
+    impl MpsseChannel for MockChannel {
+        fn enable_mpsse(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn set_clock(&mut self, clock_hz: u32) -> Result<(), Error> {
+            self.clocks.push(clock_hz);
+            Ok(())
+        }
+
+        fn gpio_lower(&mut self) -> Result<u8, Error> {
+            self.gpio_reads += 1;
+            Ok(self.pins)
+        }
+
+        fn set_gpio_lower(&mut self, state: u8, direction: u8) -> Result<(), Error> {
+            self.gpio_writes.push((state, direction));
+            Ok(())
+        }
+
+        fn set_gpio_upper(&mut self, _state: u8, _direction: u8) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn send(&mut self, cmd: &[u8]) -> Result<(), Error> {
+            self.sent.push(cmd.to_vec());
+            Ok(())
+        }
+
+        fn recv(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+            buf.fill(0);
+            Ok(())
+        }
+    }
+
+    type Backend = FtdiBackend<MockChannel>;
+
     #[test]
     fn test_pin_flags() {
         assert_eq!(0xA8, (SpiPin::SS_N | SpiPin::EN_N | SpiPin::RST_N).bits());
@@ -280,15 +569,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_facet2_pin_map() {
+        assert_eq!(
+            SpiLines::from_pin_map(&PinMap::facet2_spi()).unwrap(),
+            SpiLines::FACET2
+        );
+        assert!(matches!(
+            SpiLines::from_pin_map(&PinMap::facet2_i2c()).map_err(Error::into_kind),
+            Err(ErrorKind::MissingPin(Signal::SpiClk))
+        ));
+    }
+
+    #[test]
+    fn test_turnaround_command() {
+        let cmd = |cycles| {
+            let (cmd, len) = Backend::turnaround_command(cycles);
+            cmd[..len].to_vec()
+        };
+
+        assert_eq!(cmd(DEFAULT_TURNAROUND_CYCLES), [0x8F, 0x01, 0x00]);
+        assert_eq!(cmd(0), []);
+        assert_eq!(cmd(3), [0x8E, 0x02]);
+        assert_eq!(cmd(8), [0x8F, 0x00, 0x00]);
+        assert_eq!(cmd(19), [0x8F, 0x01, 0x00, 0x8E, 0x02]);
+        assert_eq!(cmd(0x800), [0x8F, 0xFF, 0x00]);
+    }
+
     #[test]
     fn test_set_bits_high() {
         assert_eq!(
-            FtdiBackend::set_data_bits_single(SpiPin::CLK, SpiPin::EN_N, true).unwrap(),
+            Backend::set_data_bits_single(SpiPin::CLK, SpiPin::EN_N, true).unwrap(),
             SpiPin::CLK | SpiPin::EN_N
         );
 
         assert_eq!(
-            FtdiBackend::set_data_bits_single(SpiPin::CLK, SpiPin::CLK, true).unwrap(),
+            Backend::set_data_bits_single(SpiPin::CLK, SpiPin::CLK, true).unwrap(),
             SpiPin::CLK
         );
     }
@@ -296,13 +612,88 @@ mod tests {
     #[test]
     fn test_set_bits_low() {
         assert_eq!(
-            FtdiBackend::set_data_bits_single(SpiPin::CLK, SpiPin::EN_N, false).unwrap(),
+            Backend::set_data_bits_single(SpiPin::CLK, SpiPin::EN_N, false).unwrap(),
             SpiPin::CLK
         );
 
         assert_eq!(
-            FtdiBackend::set_data_bits_single(SpiPin::CLK, SpiPin::CLK, false).unwrap(),
+            Backend::set_data_bits_single(SpiPin::CLK, SpiPin::CLK, false).unwrap(),
             SpiPin::empty()
         );
     }
+
+    #[test]
+    fn test_gpio_cache() {
+        let mut backend = Backend::new(MockChannel::default());
+        assert_eq!(backend.gpio_state(), SpiLines::FACET2.idle);
+
+        backend.set_enable(true).unwrap();
+        backend.set_chip_select(true).unwrap();
+        let expected = SpiPin::RST_N;
+        assert_eq!(backend.gpio_state(), expected);
+        assert_eq!(
+            backend.dev.gpio_writes.last(),
+            Some(&(expected.bits(), SpiLines::FACET2.directions.bits()))
+        );
+
+        // Register accesses keep the cached state, only toggling chip select around them
+        backend.set_chip_select(false).unwrap();
+        let cached = SpiPin::SS_N | SpiPin::RST_N;
+        backend.write_register(Register::Argument, 0).unwrap();
+        backend.read_register(Register::Argument).unwrap();
+        assert_eq!(backend.gpio_state(), cached);
+        assert_eq!(backend.dev.gpio_writes.len(), 3);
+        assert_eq!(backend.dev.gpio_reads, 0);
+
+        let directions = SpiLines::FACET2.directions.bits();
+        let select = [0x80, (cached & !SpiPin::SS_N).bits(), directions];
+        let release = [0x80, cached.bits(), directions];
+        let [write, read] = &backend.dev.sent[..] else {
+            panic!("expected one command stream per access");
+        };
+        assert_eq!(write[..3], select);
+        assert_eq!(write[write.len() - 3..], release);
+        assert_eq!(read[..3], select);
+        assert_eq!(read[read.len() - 4..read.len() - 1], release);
+        assert_eq!(read.last(), Some(&0x87));
+    }
+
+    #[test]
+    fn test_resync_gpio() {
+        let mut backend = Backend::new(MockChannel {
+            pins: 0xFF,
+            ..Default::default()
+        });
+        backend.set_reset(true).unwrap();
+
+        // MISO and the unused inputs read high, but are not outputs
+        let state = backend.resync_gpio().unwrap();
+        assert_eq!(state, SpiLines::FACET2.directions);
+        assert_eq!(backend.gpio_state(), state);
+
+        backend.dev.pins = SpiPin::MISO.bits();
+        assert_eq!(backend.resync_gpio().unwrap(), SpiPin::empty());
+    }
+
+    #[test]
+    fn test_settings_validation() {
+        let mut backend = Backend::new(MockChannel::default());
+        for clock_hz in [MIN_CLOCK_HZ - 1, MAX_CLOCK_HZ + 1] {
+            assert!(matches!(
+                backend.set_clock_hz(clock_hz).map_err(Error::into_kind),
+                Err(ErrorKind::UnsupportedClock { .. })
+            ));
+        }
+        assert_eq!(backend.settings(), Backend::DEFAULT_SETTINGS);
+
+        // Before initialize() the clock is only stored
+        backend.set_clock_hz(MAX_CLOCK_HZ).unwrap();
+        assert!(backend.dev.clocks.is_empty());
+        backend.initialize().unwrap();
+        backend.set_clock_hz(1_000_000).unwrap();
+        assert_eq!(backend.dev.clocks, [MAX_CLOCK_HZ, 1_000_000]);
+
+        backend.set_turnaround_cycles(u16::MAX).unwrap();
+        assert_eq!(backend.settings().turnaround_cycles, u16::MAX);
+    }
 }
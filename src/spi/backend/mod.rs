@@ -1,16 +1,66 @@
-use super::protocol::commands::Register;
+use super::protocol::commands::{Command, Register};
 use super::protocol::transaction::TransactionType;
 /// Backend abstraction module - hardware-specific implementations
 ///
 /// This module defines a common trait for SPI backends and provides
 /// implementations for both FTDI and embedded-hal.
 use crate::prelude::*;
-use crate::error::Error;
+use crate::error::{Error, ErrorKind};
 
 #[cfg(feature = "ftdi")]
 pub mod ftdi;
+pub mod bitbang;
 pub mod eh;
 
+/// Length of the command + register header in bits
+pub(crate) const HEADER_BITS: usize = (Command::bit_length() + Register::bit_length()) as usize;
+
+/// Pack command and register into the 10-bit header, LSB-first
+pub(crate) fn header(command: Command, register: u8) -> u16 {
+    u16::from(command.bits()) | (u16::from(register) << Command::bit_length())
+}
+
+/// Link parameters of a SPI backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpiSettings {
+    /// SPI clock frequency in Hz
+    pub clock_hz: u32,
+    /// Idle clock cycles between the read header and the response
+    pub turnaround_cycles: u16,
+}
+
+/// Range of [`SpiSettings`] a backend supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpiCapabilities {
+    pub min_clock_hz: u32,
+    pub max_clock_hz: u32,
+    pub min_turnaround_cycles: u16,
+    pub max_turnaround_cycles: u16,
+}
+
+impl SpiCapabilities {
+    /// Check settings against the supported range
+    pub fn validate(&self, settings: &SpiSettings) -> Result<(), Error> {
+        if !(self.min_clock_hz..=self.max_clock_hz).contains(&settings.clock_hz) {
+            return Err(ErrorKind::UnsupportedClock {
+                clock_hz: settings.clock_hz,
+            }
+            .into());
+        }
+
+        if !(self.min_turnaround_cycles..=self.max_turnaround_cycles)
+            .contains(&settings.turnaround_cycles)
+        {
+            return Err(ErrorKind::UnsupportedTurnaround {
+                cycles: settings.turnaround_cycles,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
 /// Common SPI backend trait
 ///
 /// This trait abstracts the low-level SPI operations needed for the eMMC protocol.
@@ -44,6 +94,36 @@ pub trait SpiBackend {
 
     /// Initialize the SPI interface
     fn initialize(&mut self) -> Result<(), Error>;
+
+    /// Supported range for the link settings
+    fn capabilities(&self) -> SpiCapabilities;
+
+    /// Currently active link settings
+    fn settings(&self) -> SpiSettings;
+
+    /// Change the link settings
+    ///
+    /// Settings are validated against [`SpiBackend::capabilities`] and take
+    /// effect for the next transaction.
+    fn apply_settings(&mut self, settings: SpiSettings) -> Result<(), Error>;
+
+    /// Change the SPI clock frequency (Hz)
+    fn set_clock_hz(&mut self, clock_hz: u32) -> Result<(), Error> {
+        let settings = SpiSettings {
+            clock_hz,
+            ..self.settings()
+        };
+        self.apply_settings(settings)
+    }
+
+    /// Change the number of turnaround clock cycles
+    fn set_turnaround_cycles(&mut self, cycles: u16) -> Result<(), Error> {
+        let settings = SpiSettings {
+            turnaround_cycles: cycles,
+            ..self.settings()
+        };
+        self.apply_settings(settings)
+    }
 }
 
 /// Helper trait for GPIO control (used by backends that need it)
@@ -3,11 +3,23 @@
 /// This module provides a clean, high-level API for reading from the eMMC chip,
 /// using the backend abstraction to work with any SPI implementation.
 use super::backend::SpiBackend;
+use super::poll::{DefaultClock, PollClock, PollMatch, PollOperation, PollPolicies, PollPolicy};
 use super::protocol::commands::{Register, status, transfer_config};
+use super::protocol::registers::CommandAndTransferMode;
+use super::register_scan::{DEFAULT_SCAN_DENYLIST, RegisterScan, SCAN_READ_DENYLIST};
+use super::snapshot::{CardState, RegisterReading, RegisterSnapshot};
 use crate::prelude::*;
-use crate::error::Error;
+#[cfg(feature = "sdmmc")]
+use crate::block_device::{Block, BlockCount, BlockDevice, BlockIdx};
+use crate::error::{Error, ErrorKind};
 use crate::DelayTrait;
 
+/// Number of 512-byte pages on the console eMMC
+pub const DEFAULT_BLOCK_COUNT: u32 = 0x9E0000;
+
+/// Relative card address assigned with CMD3 during the init sequence
+const CARD_RCA: u32 = 0xA;
+
 //Development Mode, SMCFWKey:Devkit
 const B1SMCBL_HASH_DEVKIT: [u8; 16] = hex_literal::hex!("C0DE15B90000FFFFA5A55A5A1234FEDC");
 //# Production Mode, SMCFWKey:rtlA
@@ -29,33 +41,52 @@ pub struct SMC_FUSES {
     SbRev: [u8; 4],
 }
 
-#[cfg(feature = "std")]
-impl std::fmt::Display for SMC_FUSES {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+/// Lowercase hex formatting of a byte slice, without allocating
+struct Hex<'a>(&'a [u8]);
+
+impl core::fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl core::fmt::Display for SMC_FUSES {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "ECID: {}", Hex(&self.ECID))?;
+
         let smc_flavor = match self.Exp1SMCBLDigest {
-            B1SMCBL_HASH_DEVKIT => "Development Mode, SMCFWKey:Devkit".to_string(),
-            B1SMCBL_HASH_RTL_A => "Production Mode, SMCFWKey:rtlA".to_string(),
-            B1SMCBL_HASH_RTL_B => "Production Mode, SMCFWKey:rtlB".to_string(),
-            B1SMCBL_HASH_RTL_C => "Production Mode, SMCFWKey:rtlC".to_string(),
-            B1SMCBL_HASH_RTL_D => "Production Mode, SMCFWKey:rtlD".to_string(),
-            _ => format!("!UNKNOWN! ({})", hex::encode(self.Exp1SMCBLDigest)),
+            B1SMCBL_HASH_DEVKIT => "Development Mode, SMCFWKey:Devkit",
+            B1SMCBL_HASH_RTL_A => "Production Mode, SMCFWKey:rtlA",
+            B1SMCBL_HASH_RTL_B => "Production Mode, SMCFWKey:rtlB",
+            B1SMCBL_HASH_RTL_C => "Production Mode, SMCFWKey:rtlC",
+            B1SMCBL_HASH_RTL_D => "Production Mode, SMCFWKey:rtlD",
+            _ => "",
         };
+        if smc_flavor.is_empty() {
+            writeln!(f, "Exp1SMCBLDigest: !UNKNOWN! ({})", Hex(&self.Exp1SMCBLDigest))?;
+        } else {
+            writeln!(f, "Exp1SMCBLDigest: {smc_flavor}")?;
+        }
 
-        writeln!(f, "ECID: {}", hex::encode(self.ECID))?;
-        writeln!(f, "Exp1SMCBLDigest: {smc_flavor}")?;
-        writeln!(f, "RsvdPublic: {}", hex::encode(self.RsvdPublic))?;
-        writeln!(f, "RsvdPrivate: {}", hex::encode(self.RsvdPrivate))?;
-        writeln!(f, "ChipID: {}", hex::encode(self.ChipID))?;
-        writeln!(f, "SB Rev: {}", hex::encode(self.SbRev))?;
+        writeln!(f, "RsvdPublic: {}", Hex(&self.RsvdPublic))?;
+        writeln!(f, "RsvdPrivate: {}", Hex(&self.RsvdPrivate))?;
+        writeln!(f, "ChipID: {}", Hex(&self.ChipID))?;
+        writeln!(f, "SB Rev: {}", Hex(&self.SbRev))?;
         Ok(())
     }
 }
 
 /// eMMC SPI Reader - works with any backend
-pub struct EmmcReader<B: SpiBackend, D: DelayTrait> {
+pub struct EmmcReader<B: SpiBackend, D: DelayTrait, C: PollClock = DefaultClock> {
     pub backend: B,
     initialized: bool,
     delay: D,
+    clock: C,
+    block_count: u32,
+    poll_policies: PollPolicies,
 }
 
 impl<B: SpiBackend, D: DelayTrait> EmmcReader<B, D> {
@@ -65,18 +96,91 @@ impl<B: SpiBackend, D: DelayTrait> EmmcReader<B, D> {
             backend,
             initialized: false,
             delay: delay_impl,
+            clock: DefaultClock::default(),
+            block_count: DEFAULT_BLOCK_COUNT,
+            poll_policies: PollPolicies::default(),
+        }
+    }
+}
+
+impl<B: SpiBackend, D: DelayTrait, C: PollClock> EmmcReader<B, D, C> {
+    /// Measure poll timeouts with `clock` instead of the default clock
+    pub fn with_clock<C2: PollClock>(self, clock: C2) -> EmmcReader<B, D, C2> {
+        EmmcReader {
+            backend: self.backend,
+            initialized: self.initialized,
+            delay: self.delay,
+            clock,
+            block_count: self.block_count,
+            poll_policies: self.poll_policies,
         }
     }
 
+    /// Poll policy used while waiting for `operation`
+    pub fn poll_policy(&self, operation: PollOperation) -> PollPolicy {
+        self.poll_policies.get(operation)
+    }
+
+    /// Override the poll policy of a single operation
+    pub fn set_poll_policy(&mut self, operation: PollOperation, policy: PollPolicy) {
+        self.poll_policies.set(operation, policy);
+    }
+
+    /// Override the number of pages reported by [`EmmcBlockDevice`]
+    pub fn set_block_count(&mut self, block_count: u32) {
+        self.block_count = block_count;
+    }
+
     fn open(&mut self) {}
     fn close(&mut self) {}
     fn controller_init(&mut self) {}
     fn initialize_controller_clock(&mut self) {}
     fn mmc_init(&mut self) {}
-    fn mmc_enter_standby_mode(&mut self) {}
-    fn mmc_select_card(&mut self) {}
-    fn mmc_command(&mut self) {}
-    fn mmc_get_cid(&mut self) {}
+
+    /// Deselect the card (CMD7 with RCA 0), moving it to standby
+    fn mmc_enter_standby_mode(&mut self) -> Result<(), Error> {
+        self.mmc_command(transfer_config::DESELECT_CARD, 0)?;
+        Ok(())
+    }
+
+    /// Select the card (CMD7), moving it back to transfer state
+    fn mmc_select_card(&mut self) -> Result<(), Error> {
+        self.mmc_command(transfer_config::SELECT_CARD, CARD_RCA << 16)?;
+        Ok(())
+    }
+
+    /// Issue a command without data and return the response registers
+    ///
+    /// `command` is the [`Register::CommandAndTransferMode`] value, e.g. one
+    /// of [`transfer_config`].
+    fn mmc_command(&mut self, command: u32, argument: u32) -> Result<[u32; 4], Error> {
+        self.mmc_command_sequence(command, argument)
+            .map_err(|e| e.with_command(CommandAndTransferMode(command).command_index()))
+    }
+
+    fn mmc_command_sequence(&mut self, command: u32, argument: u32) -> Result<[u32; 4], Error> {
+        self.write_register(Register::Argument, argument)?;
+        self.write_register(Register::CommandAndTransferMode, command)?;
+
+        self.poll_for_value(Register::InterruptStatus, status::CMD_BUSY)?;
+        self.write_register(Register::InterruptStatus, status::CMD_BUSY)?;
+
+        let mut response = [0u32; 4];
+        for (index, word) in response.iter_mut().enumerate() {
+            *word = self.read_response(index as u8)?;
+        }
+        Ok(response)
+    }
+
+    /// Read the CID (card must be in standby)
+    fn mmc_get_cid(&mut self) -> Result<[u32; 4], Error> {
+        self.mmc_command(transfer_config::SEND_CID, CARD_RCA << 16)
+    }
+
+    /// Read the CSD (card must be in standby)
+    fn mmc_get_csd(&mut self) -> Result<[u32; 4], Error> {
+        self.mmc_command(transfer_config::SEND_CSD, CARD_RCA << 16)
+    }
     fn mmc_read_extended_csd(&mut self) {}
     fn mmc_set_block_size(&mut self, block_size: u32) {}
     fn mmc_set_block_count(&mut self, block_count: u32) {}
@@ -85,9 +189,20 @@ impl<B: SpiBackend, D: DelayTrait> EmmcReader<B, D> {
     fn mmc_partition(&mut self) {}
     fn mmc_poll_status_bit(&mut self, bit: u32) {}
     fn mmc_poll_status_bitmask(&mut self, mask: u32) {}
-    fn mmc_register_print(&mut self) {}
+
+    /// Print the controller registers, see [`EmmcReader::dump_mmc_registers`]
+    pub fn mmc_register_print<W: core::fmt::Write>(&mut self, out: &mut W) -> Result<(), Error> {
+        let snapshot = self.dump_mmc_registers()?;
+        write!(out, "{snapshot}").map_err(|_| Error::from(ErrorKind::Format))
+    }
+
     fn mmc_sanitize(&mut self) {}
-    fn mmc_send_status(&mut self) {}
+
+    /// Read the card status (CMD13)
+    fn mmc_send_status(&mut self) -> Result<u32, Error> {
+        let response = self.mmc_command(transfer_config::SEND_STATUS, CARD_RCA << 16)?;
+        Ok(response[0])
+    }
     fn clear_interrupt_status(&mut self) {}
 
     fn set_output_delay(&mut self, delay: u32) {}
@@ -103,7 +218,7 @@ impl<B: SpiBackend, D: DelayTrait> EmmcReader<B, D> {
         let mut buf = [0u8; 0x38];
 
         let mut pos = 0;
-        for reg in Register::XipDataFirst.address()..=Register::XipDataLast.address() {
+        for reg in Register::XIP_DATA {
             let value = self.backend.read_register(reg)?;
             buf[pos..pos + size_of::<u32>()].copy_from_slice(&value.to_le_bytes());
             pos += size_of::<u32>();
@@ -134,7 +249,48 @@ impl<B: SpiBackend, D: DelayTrait> EmmcReader<B, D> {
 
         Ok(fuses)
     }
-    fn dump_mmc_registers(&mut self) {}
+
+    /// Capture all known controller registers
+    ///
+    /// [`Register::DataFifo`] is not read and no commands are sent to the
+    /// card, see [`EmmcReader::dump_mmc_registers_with_card`].
+    pub fn dump_mmc_registers(&mut self) -> Result<RegisterSnapshot, Error> {
+        let mut registers = Register::ALL.map(|register| RegisterReading {
+            register,
+            value: None,
+        });
+        for reading in registers.iter_mut() {
+            if reading.register != Register::DataFifo {
+                reading.value = Some(self.read_register(reading.register)?);
+            }
+        }
+
+        Ok(RegisterSnapshot {
+            registers,
+            card: None,
+        })
+    }
+
+    /// Like [`EmmcReader::dump_mmc_registers`], plus CID, CSD and status once initialized
+    ///
+    /// Reading CID and CSD deselects the card (CMD7 with RCA 0) and selects it
+    /// again. Only [`transfer_config::SELECT_CARD`] is confirmed by a trace,
+    /// the other command words follow its encoding.
+    pub fn dump_mmc_registers_with_card(&mut self) -> Result<RegisterSnapshot, Error> {
+        let mut snapshot = self.dump_mmc_registers()?;
+
+        if self.is_initialized() {
+            self.mmc_enter_standby_mode()?;
+            let cid = self.mmc_get_cid()?;
+            let csd = self.mmc_get_csd()?;
+            self.mmc_select_card()?;
+            let status = self.mmc_send_status()?;
+
+            snapshot.card = Some(CardState { cid, csd, status });
+        }
+
+        Ok(snapshot)
+    }
 
     /// Send init sequence
     ///
@@ -322,10 +478,11 @@ impl<B: SpiBackend, D: DelayTrait> EmmcReader<B, D> {
             let response1 = self.backend.read_register(Register::Argument)?;
 
             if response1 != test_value {
-                return Err(Error::SanityCheckFailed {
+                return Err(Error::from(ErrorKind::SanityCheckFailed {
                     expected: test_value,
                     actual: response1,
-                });
+                })
+                .with_register(Register::Argument.address()));
             }
         }
 
@@ -334,17 +491,23 @@ impl<B: SpiBackend, D: DelayTrait> EmmcReader<B, D> {
 
     /// Write a value to a register
     pub fn write_register(&mut self, register: Register, value: u32) -> Result<(), Error> {
-        self.backend.write_register(register, value)
+        self.backend
+            .write_register(register, value)
+            .map_err(|e| e.with_register(register.address()))
     }
 
     /// Read a value from a register
     pub fn read_register(&mut self, register: Register) -> Result<u32, Error> {
-        self.backend.read_register(register)
+        self.backend
+            .read_register(register)
+            .map_err(|e| e.with_register(register.address()))
     }
 
     /// Read a 512-byte block
     pub fn read_data(&mut self, register: Register, buffer: &mut [u8]) -> Result<(), Error> {
-        self.backend.read_data(register, buffer)
+        self.backend
+            .read_data(register, buffer)
+            .map_err(|e| e.with_register(register.address()))
     }
 
     /// Read the present state register
@@ -369,28 +532,82 @@ impl<B: SpiBackend, D: DelayTrait> EmmcReader<B, D> {
             1 => Register::Response2And3,
             2 => Register::Response4And5,
             3 => Register::Response6And7,
-            _ => return Err(Error::RegisterAccessFailed),
+            _ => return Err(ErrorKind::InvalidResponseIndex { index }.into()),
         };
 
         self.read_register(register)
     }
 
+    /// Read every controller address and probe its writable bits
+    ///
+    /// Registers in [`DEFAULT_SCAN_DENYLIST`] are only read.
+    pub fn scan_registers(&mut self) -> Result<RegisterScan, Error> {
+        self.scan_registers_with(&DEFAULT_SCAN_DENYLIST)
+    }
+
+    /// Like [`EmmcReader::scan_registers`], with a custom list of addresses not to write
+    ///
+    /// Each probed address gets the inverted value written, read back and
+    /// restored. Addresses in [`SCAN_READ_DENYLIST`] are never touched.
+    pub fn scan_registers_with(&mut self, denylist: &[u8]) -> Result<RegisterScan, Error> {
+        let mut scan = RegisterScan::new();
+
+        for entry in scan.entries.iter_mut() {
+            let address = entry.address;
+            if SCAN_READ_DENYLIST.contains(&address) {
+                continue;
+            }
+
+            let original = self.backend.read_register(address)?;
+            entry.value = Some(original);
+
+            if denylist.contains(&address) {
+                continue;
+            }
+
+            let probed = self
+                .backend
+                .write_register(address, !original)
+                .and_then(|()| self.backend.read_register(address));
+            // Restore even if probing failed, then report the first error
+            let restored = self.backend.write_register(address, original);
+            let probed = probed?;
+            restored?;
+
+            entry.writable = Some(probed ^ original);
+            entry.restored = self.backend.read_register(address)? == original;
+        }
+
+        Ok(scan)
+    }
+
     /// Check if initialization is complete
     pub fn is_initialized(&self) -> bool {
         self.initialized
     }
 
+    /// Poll until `register` equals `value`, using the command poll policy
     pub fn poll_for_value(&mut self, register: Register, value: u32) -> Result<(), Error> {
-        const MAX_POLLS: u32 = 10;
-        for _ in 0..MAX_POLLS {
-            let status_value = self.read_register(register)?;
-            if status_value == value {
-                return Ok(());
-            }
-            self.delay.delay_ms(10);
-        }
+        self.poll_register(register, PollMatch::Exact(value), PollOperation::Command)?;
+        Ok(())
+    }
 
-        Err(Error::Timeout)
+    /// Poll until `register` meets `condition`, returning the matching value
+    ///
+    /// Timing follows the policy set for `operation`, see [`EmmcReader::set_poll_policy`].
+    pub fn poll_register(
+        &mut self,
+        register: Register,
+        condition: PollMatch,
+        operation: PollOperation,
+    ) -> Result<u32, Error> {
+        let backend = &mut self.backend;
+        self.poll_policies
+            .get(operation)
+            .poll(register, condition, &mut self.delay, &mut self.clock, || {
+                backend.read_register(register)
+            })
+            .map_err(|e| e.with_register(register.address()))
     }
 
     /// Read a page from the eMMC chip
@@ -408,6 +625,12 @@ impl<B: SpiBackend, D: DelayTrait> EmmcReader<B, D> {
     /// * `page_number` - The page number to read
     /// * `buffer` - Buffer to store the 512-byte page
     pub fn read_page(&mut self, page_number: u32, buffer: &mut [u8; 512]) -> Result<(), Error> {
+        let command = CommandAndTransferMode(transfer_config::PAGE_READ).command_index();
+        self.read_page_sequence(page_number, buffer)
+            .map_err(|e| e.with_page(page_number).with_command(command))
+    }
+
+    fn read_page_sequence(&mut self, page_number: u32, buffer: &mut [u8; 512]) -> Result<(), Error> {
         // Step 1: Clear/reset status
         self.write_register(Register::InterruptStatus, status::STATUS_CLEAR)?;
 
@@ -418,10 +641,18 @@ impl<B: SpiBackend, D: DelayTrait> EmmcReader<B, D> {
         self.write_register(Register::CommandAndTransferMode, transfer_config::PAGE_READ)?;
 
         // Step 4: Poll for command accepted
-        self.poll_for_value(Register::InterruptStatus, status::CMD_ACCEPTED)?;
+        self.poll_register(
+            Register::InterruptStatus,
+            PollMatch::Exact(status::CMD_ACCEPTED),
+            PollOperation::Read,
+        )?;
 
         // Step 5: Poll for data ready and send interrupt acknowledge
-        self.poll_for_value(Register::InterruptStatus, status::DATA_READY)?;
+        self.poll_register(
+            Register::InterruptStatus,
+            PollMatch::Exact(status::DATA_READY),
+            PollOperation::Read,
+        )?;
         self.write_register(Register::InterruptStatus, status::DATA_READY)?;
 
         // Step 6: Read 512-byte block from data FIFO
@@ -468,12 +699,20 @@ impl<B: SpiBackend, D: DelayTrait> EmmcReader<B, D> {
         self.write_register(Register::CommandAndTransferMode, ERASE_TRANSFER_CONFIG)?;
 
         // Step 4: Poll for command accepted
-        self.poll_for_value(Register::InterruptStatus, status::CMD_ACCEPTED)?;
+        self.poll_register(
+            Register::InterruptStatus,
+            PollMatch::Exact(status::CMD_ACCEPTED),
+            PollOperation::Erase,
+        )?;
 
         // Step 5: Poll for erase complete
         // TODO: Determine the correct status value for erase completion
         // Erasing typically takes longer than reading
-        self.poll_for_value(Register::InterruptStatus, status::TRANSFER_COMPLETE)?;
+        self.poll_register(
+            Register::InterruptStatus,
+            PollMatch::Exact(status::TRANSFER_COMPLETE),
+            PollOperation::Erase,
+        )?;
         self.write_register(Register::InterruptStatus, status::TRANSFER_COMPLETE)?;
 
         todo!("WARNING: erase_page is a STUB - protocol sequence not yet validated");
@@ -517,7 +756,11 @@ impl<B: SpiBackend, D: DelayTrait> EmmcReader<B, D> {
         self.write_register(Register::CommandAndTransferMode, WRITE_TRANSFER_CONFIG)?;
 
         // Step 4: Poll for command accepted
-        self.poll_for_value(Register::InterruptStatus, status::CMD_ACCEPTED)?;
+        self.poll_register(
+            Register::InterruptStatus,
+            PollMatch::Exact(status::CMD_ACCEPTED),
+            PollOperation::Write,
+        )?;
 
         // Step 5: Write 512-byte block to data FIFO
         // TODO: Implement write_data method in backend trait
@@ -527,7 +770,11 @@ impl<B: SpiBackend, D: DelayTrait> EmmcReader<B, D> {
 
         // Step 6: Poll for write complete
         // TODO: Determine if there's a specific status for write ready/complete
-        self.poll_for_value(Register::InterruptStatus, status::TRANSFER_COMPLETE)?;
+        self.poll_register(
+            Register::InterruptStatus,
+            PollMatch::Exact(status::TRANSFER_COMPLETE),
+            PollOperation::Write,
+        )?;
         self.write_register(Register::InterruptStatus, status::TRANSFER_COMPLETE)?;
 
         todo!("WARNING: write_page is a STUB - protocol sequence not yet validated");
@@ -539,41 +786,113 @@ impl<B: SpiBackend, D: DelayTrait> EmmcReader<B, D> {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::DelayTrait;
+/// [`EmmcReader`] as an `embedded-sdmmc` block device
+///
+/// Pages are exposed as 512-byte blocks. `BlockDevice` takes `&self`, so the
+/// reader is kept in a `RefCell`, the same way `embedded_sdmmc::SdCard` does.
+#[cfg(feature = "sdmmc")]
+pub struct EmmcBlockDevice<B: SpiBackend, D: DelayTrait, C: PollClock = DefaultClock> {
+    reader: core::cell::RefCell<EmmcReader<B, D, C>>,
+}
 
-    // Mock delay
-    struct MockDelay;
+#[cfg(feature = "sdmmc")]
+impl<B: SpiBackend, D: DelayTrait, C: PollClock> EmmcBlockDevice<B, D, C> {
+    pub fn new(reader: EmmcReader<B, D, C>) -> Self {
+        Self {
+            reader: core::cell::RefCell::new(reader),
+        }
+    }
 
-    impl DelayTrait for MockDelay {
-        fn delay_ns(&mut self, ns: u32) {}
+    pub fn into_inner(self) -> EmmcReader<B, D, C> {
+        self.reader.into_inner()
     }
+}
+
+#[cfg(feature = "sdmmc")]
+impl<B: SpiBackend, D: DelayTrait, C: PollClock> BlockDevice for EmmcBlockDevice<B, D, C> {
+    type Error = Error;
+
+    fn read(&self, blocks: &mut [Block], start_block_idx: BlockIdx) -> Result<(), Error> {
+        let mut reader = self.reader.borrow_mut();
+        let start = start_block_idx.0;
+        let end = start
+            .checked_add(blocks.len() as u32)
+            .filter(|&end| end <= reader.block_count)
+            .ok_or(ErrorKind::OutOfBounds)?;
+
+        for (page, block) in (start..end).zip(blocks.iter_mut()) {
+            reader.read_page(page, &mut block.contents)?;
+        }
+        Ok(())
+    }
+
+    fn write(&self, _blocks: &[Block], _start_block_idx: BlockIdx) -> Result<(), Error> {
+        // write_page is not validated against hardware yet
+        Err(ErrorKind::Unsupported {
+            operation: "eMMC write",
+        }
+        .into())
+    }
+
+    fn num_blocks(&self) -> Result<BlockCount, Error> {
+        Ok(BlockCount(self.reader.borrow().block_count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::NoDelay;
+    use crate::spi::backend::{SpiCapabilities, SpiSettings};
+    use crate::error::ErrorContext;
+    use crate::spi::protocol::commands::MmcState;
+    use crate::spi::poll::NoClock;
 
     // Mock backend for testing
     struct MockBackend {
-        registers: std::collections::HashMap<u8, u32>,
+        registers: [u32; 256],
+        /// Bits that accept writes, per address
+        writable: [u32; 256],
+        /// Address whose reads fail after the given number of successful reads
+        read_fault: Option<(u8, u32)>,
         initialized: bool,
+        settings: SpiSettings,
     }
 
     impl MockBackend {
         fn new() -> Self {
             Self {
-                registers: std::collections::HashMap::new(),
+                registers: [0; 256],
+                writable: [u32::MAX; 256],
+                read_fault: None,
                 initialized: false,
+                settings: SpiSettings {
+                    clock_hz: 1_000_000,
+                    turnaround_cycles: 16,
+                },
             }
         }
     }
 
     impl SpiBackend for MockBackend {
         fn write_register<T: Into<u8>>(&mut self, register: T, data: u32) -> Result<(), Error> {
-            self.registers.insert(register.into(), data);
+            let address = usize::from(register.into());
+            let writable = self.writable[address];
+            self.registers[address] = (self.registers[address] & !writable) | (data & writable);
             Ok(())
         }
 
         fn read_register<T: Into<u8>>(&mut self, register: T) -> Result<u32, Error> {
-            Ok(*self.registers.get(&register.into()).unwrap_or(&0))
+            let address = register.into();
+            if let Some((fault, remaining)) = &mut self.read_fault
+                && *fault == address
+            {
+                if *remaining == 0 {
+                    return Err(ErrorKind::Timeout.into());
+                }
+                *remaining -= 1;
+            }
+            Ok(self.registers[usize::from(address)])
         }
 
         fn read_data<T: Into<u8>>(&mut self, _register: T, buffer: &mut [u8]) -> Result<(), Error> {
@@ -589,11 +908,30 @@ mod tests {
             self.initialized = true;
             Ok(())
         }
+
+        fn capabilities(&self) -> SpiCapabilities {
+            SpiCapabilities {
+                min_clock_hz: 1_000,
+                max_clock_hz: 10_000_000,
+                min_turnaround_cycles: 1,
+                max_turnaround_cycles: 64,
+            }
+        }
+
+        fn settings(&self) -> SpiSettings {
+            self.settings
+        }
+
+        fn apply_settings(&mut self, settings: SpiSettings) -> Result<(), Error> {
+            self.capabilities().validate(&settings)?;
+            self.settings = settings;
+            Ok(())
+        }
     }
 
     #[test]
     fn test_read_write() {
-        let delay_impl = MockDelay;
+        let delay_impl = NoDelay;
         let backend = MockBackend::new();
         let mut reader = EmmcReader::new(backend, delay_impl);
         // reader.init().unwrap();
@@ -605,4 +943,191 @@ mod tests {
         let value = reader.read_register(Register::Argument).unwrap();
         assert_eq!(value, 0xDEADBEEF);
     }
+
+    #[test]
+    fn test_scan_registers() {
+        let config = usize::from(Register::Config1.address());
+        let mut backend = MockBackend::new();
+        backend.registers[config] = 0x800000;
+        backend.writable[config] = 0x0000_00FF;
+        backend.writable[0x50] = 0;
+        backend.registers[usize::from(Register::InterruptStatus.address())] = 0x1;
+        backend.registers[usize::from(Register::Reg_0A.address())] = 0x800020;
+        let before = backend.registers;
+
+        let mut reader = EmmcReader::new(backend, NoDelay);
+        let scan = reader.scan_registers().unwrap();
+
+        let entry = &scan.entries[config];
+        assert_eq!(entry.value, Some(0x800000));
+        assert_eq!(entry.writable, Some(0xFF));
+        assert!(entry.restored);
+        assert_eq!(scan.entries[0x50].writable, Some(0));
+
+        // Denylisted registers are read but never written, the FIFO is not touched
+        let status = &scan.entries[usize::from(Register::InterruptStatus.address())];
+        assert_eq!(status.value, Some(0x1));
+        assert_eq!(status.writable, None);
+        let power = &scan.entries[usize::from(Register::Reg_0A.address())];
+        assert_eq!(power.value, Some(0x800020));
+        assert_eq!(power.writable, None);
+        assert_eq!(scan.entries[usize::from(Register::DataFifo.address())].value, None);
+
+        assert_eq!(reader.backend.registers, before);
+    }
+
+    #[test]
+    fn test_scan_restores_after_error() {
+        let config = Register::Config1.address();
+        let mut backend = MockBackend::new();
+        backend.registers[usize::from(config)] = 0x1234;
+        // The original value reads fine, reading back the probe fails
+        backend.read_fault = Some((config, 1));
+
+        let mut reader = EmmcReader::new(backend, NoDelay);
+        assert!(matches!(
+            reader.scan_registers().map_err(Error::into_kind),
+            Err(ErrorKind::Timeout)
+        ));
+        assert_eq!(reader.backend.registers[usize::from(config)], 0x1234);
+    }
+
+    #[test]
+    fn test_dump_mmc_registers() {
+        let mut backend = MockBackend::new();
+        backend.registers[usize::from(Register::Reg_0A.address())] = 0x800020;
+        // Command complete stays set, so every command finishes immediately
+        let status = usize::from(Register::InterruptStatus.address());
+        backend.registers[status] = status::CMD_BUSY;
+        backend.writable[status] = 0;
+        backend.registers[usize::from(Register::Response0And1.address())] = 0x900;
+
+        let mut reader = EmmcReader::new(backend, NoDelay);
+        let snapshot = reader.dump_mmc_registers().unwrap();
+        assert_eq!(snapshot.get(Register::Reg_0A), Some(0x800020));
+        assert_eq!(snapshot.get(Register::DataFifo), None);
+        assert_eq!(snapshot.card, None);
+
+        reader.initialized = true;
+        let written = reader.backend.registers;
+        assert_eq!(reader.dump_mmc_registers().unwrap().card, None);
+        assert_eq!(reader.backend.registers, written);
+
+        let card = reader.dump_mmc_registers_with_card().unwrap().card.unwrap();
+        assert_eq!(card.cid, [0x900, 0, 0, 0]);
+        assert_eq!(card.state(), Some(MmcState::Transfer));
+        assert_eq!(
+            reader.read_register(Register::CommandAndTransferMode).unwrap(),
+            transfer_config::SEND_STATUS
+        );
+
+        let mut text = String::new();
+        reader.mmc_register_print(&mut text).unwrap();
+        assert!(text.contains("Reg_0A: 0x00800020"));
+        assert!(!text.contains("Card status"));
+    }
+
+    #[test]
+    fn test_read_page_poll_timeout() {
+        // No command ever completes
+        let mut backend = MockBackend::new();
+        backend.writable[usize::from(Register::InterruptStatus.address())] = 0;
+
+        let mut reader = EmmcReader::new(backend, NoDelay).with_clock(NoClock);
+        reader.set_poll_policy(
+            PollOperation::Read,
+            PollPolicy {
+                timeout: Duration::from_millis(1),
+                ..PollPolicy::FAST
+            },
+        );
+
+        let mut page = [0u8; 512];
+        let err = reader.read_page(7, &mut page).unwrap_err();
+        assert!(err.is_transient());
+        assert_eq!(
+            err.context(),
+            ErrorContext {
+                register: Some(Register::InterruptStatus.address()),
+                page: Some(7),
+                command: Some(17),
+            }
+        );
+        assert!(matches!(
+            err.into_kind(),
+            ErrorKind::PollTimeout {
+                register: Register::InterruptStatus,
+                last_value: 0,
+                elapsed,
+            } if elapsed == Duration::from_millis(1)
+        ));
+    }
+
+    #[cfg(feature = "sdmmc")]
+    #[test]
+    fn test_block_device_bounds() {
+        let mut reader = EmmcReader::new(MockBackend::new(), NoDelay);
+        reader.set_block_count(8);
+        let device = EmmcBlockDevice::new(reader);
+        assert_eq!(device.num_blocks().unwrap(), BlockCount(8));
+
+        let mut blocks = [Block::new(), Block::new()];
+        assert!(matches!(
+            device.read(&mut blocks, BlockIdx(7)).map_err(Error::into_kind),
+            Err(ErrorKind::OutOfBounds)
+        ));
+        assert!(matches!(
+            device.write(&blocks, BlockIdx(0)).map_err(Error::into_kind),
+            Err(ErrorKind::Unsupported { .. })
+        ));
+    }
+
+    #[test]
+    fn test_fuses_display() {
+        let mut fuses = SMC_FUSES {
+            ECID: [0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF],
+            Exp1SMCBLDigest: B1SMCBL_HASH_RTL_B,
+            RsvdPublic: [0; 8],
+            RsvdPrivate: [0; 8],
+            ChipID: [0; 12],
+            SbRev: [0x00, 0x00, 0x01, 0x02],
+        };
+
+        let text = format!("{fuses}");
+        assert!(text.starts_with("ECID: 0123456789abcdef\n"));
+        assert!(text.contains("Exp1SMCBLDigest: Production Mode, SMCFWKey:rtlB\n"));
+        assert!(text.ends_with("SB Rev: 00000102\n"));
+
+        fuses.Exp1SMCBLDigest = [0xAA; 16];
+        let text = format!("{fuses}");
+        assert!(text.contains("!UNKNOWN! (aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa)"));
+    }
+
+    #[test]
+    fn test_apply_settings() {
+        let mut backend = MockBackend::new();
+
+        backend.set_clock_hz(4_000_000).unwrap();
+        backend.set_turnaround_cycles(8).unwrap();
+        assert_eq!(
+            backend.settings(),
+            SpiSettings {
+                clock_hz: 4_000_000,
+                turnaround_cycles: 8,
+            }
+        );
+
+        assert!(matches!(
+            backend.set_clock_hz(20_000_000).map_err(Error::into_kind),
+            Err(ErrorKind::UnsupportedClock { clock_hz: 20_000_000 })
+        ));
+        assert!(matches!(
+            backend.set_turnaround_cycles(0).map_err(Error::into_kind),
+            Err(ErrorKind::UnsupportedTurnaround { cycles: 0 })
+        ));
+
+        // Rejected settings leave the active ones untouched
+        assert_eq!(backend.settings().clock_hz, 4_000_000);
+        assert_eq!(backend.settings().turnaround_cycles, 8);
+    }
 }
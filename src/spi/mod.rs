@@ -1,3 +1,6 @@
 pub mod protocol;
 pub mod backend;
 pub mod emmc_reader;
+pub mod poll;
+pub mod register_scan;
+pub mod snapshot;
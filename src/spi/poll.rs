@@ -0,0 +1,347 @@
+/// Register polling policies
+///
+/// Controller operations finish asynchronously and are awaited by polling a
+/// status register. How long to wait differs a lot: a command completes in
+/// microseconds, an erase or sanitize can take seconds. A [`PollPolicy`]
+/// describes the timing, a [`PollMatch`] the value being waited for.
+///
+/// Elapsed time is taken from a [`PollClock`], [`StdClock`] under `std`.
+/// Without a clock ([`NoClock`]) it is the sum of the delays between polls;
+/// bus transfers are then not counted and the real wall time is somewhat
+/// longer than the configured timeout.
+use crate::prelude::*;
+use super::protocol::commands::Register;
+use crate::error::{Error, ErrorKind};
+use crate::DelayTrait;
+
+/// Time source for poll timeouts
+pub trait PollClock {
+    /// Time since an arbitrary, fixed point
+    fn now(&mut self) -> Duration;
+}
+
+/// No clock, elapsed time is the sum of the delays between polls
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoClock;
+
+impl PollClock for NoClock {
+    fn now(&mut self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+/// Wall clock based on [`std::time::Instant`]
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+pub struct StdClock {
+    start: std::time::Instant,
+}
+
+#[cfg(feature = "std")]
+impl Default for StdClock {
+    fn default() -> Self {
+        Self {
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl PollClock for StdClock {
+    fn now(&mut self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Clock used unless another one is set
+#[cfg(feature = "std")]
+pub type DefaultClock = StdClock;
+/// Clock used unless another one is set
+#[cfg(not(feature = "std"))]
+pub type DefaultClock = NoClock;
+
+/// Condition a polled register value has to meet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollMatch {
+    /// The whole register equals the value
+    Exact(u32),
+    /// The bits selected by `mask` equal `value`
+    Mask { mask: u32, value: u32 },
+    /// At least one of the bits is set
+    AnyBit(u32),
+}
+
+impl PollMatch {
+    pub const fn matches(&self, value: u32) -> bool {
+        match *self {
+            PollMatch::Exact(expected) => value == expected,
+            PollMatch::Mask { mask, value: expected } => value & mask == expected & mask,
+            PollMatch::AnyBit(bits) => value & bits != 0,
+        }
+    }
+}
+
+/// Timing of a poll loop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollPolicy {
+    /// Give up once this much time was spent waiting
+    pub timeout: Duration,
+    /// Delay after the first unsuccessful poll
+    pub interval: Duration,
+    /// Factor the interval is multiplied with after every unsuccessful poll, 1 keeps it constant
+    pub backoff: u32,
+    /// Upper limit for the interval when backing off
+    pub max_interval: Duration,
+}
+
+impl PollPolicy {
+    /// 10 polls 10ms apart, the timing used before policies existed
+    pub const DEFAULT: Self = Self {
+        timeout: Duration::from_millis(90),
+        interval: Duration::from_millis(10),
+        backoff: 1,
+        max_interval: Duration::from_millis(10),
+    };
+
+    /// Aggressive polling for page reads, starting at 50us
+    pub const FAST: Self = Self {
+        timeout: Duration::from_millis(100),
+        interval: Duration::from_micros(50),
+        backoff: 2,
+        max_interval: Duration::from_millis(5),
+    };
+
+    /// Slow polling for erase, program and sanitize
+    pub const LONG: Self = Self {
+        timeout: Duration::from_secs(60),
+        interval: Duration::from_millis(1),
+        backoff: 2,
+        max_interval: Duration::from_millis(100),
+    };
+
+    /// Read `register` through `read` until it meets `condition`, returning the matching value
+    ///
+    /// Elapsed time is measured with `clock`, but never counts less than the
+    /// delays requested so far.
+    pub fn poll<D, C, F>(
+        &self,
+        register: Register,
+        condition: PollMatch,
+        delay: &mut D,
+        clock: &mut C,
+        mut read: F,
+    ) -> Result<u32, Error>
+    where
+        D: DelayTrait,
+        C: PollClock,
+        F: FnMut() -> Result<u32, Error>,
+    {
+        let start = clock.now();
+        let mut waited = Duration::ZERO;
+        let mut interval = self.interval;
+
+        loop {
+            let value = read()?;
+            if condition.matches(value) {
+                return Ok(value);
+            }
+            let elapsed = clock.now().saturating_sub(start).max(waited);
+            if elapsed >= self.timeout {
+                return Err(ErrorKind::PollTimeout {
+                    register,
+                    last_value: value,
+                    elapsed,
+                }
+                .into());
+            }
+
+            let wait = interval.min(self.timeout - elapsed);
+            delay.delay_us(wait.as_micros().try_into().unwrap_or(u32::MAX));
+            waited += wait;
+            interval = interval
+                .saturating_mul(self.backoff.max(1))
+                .min(self.max_interval);
+        }
+    }
+}
+
+impl Default for PollPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Operation a poll belongs to, see [`PollPolicies`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollOperation {
+    /// Commands without data transfer
+    Command,
+    Read,
+    Write,
+    Erase,
+    Sanitize,
+}
+
+/// Poll policy per operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollPolicies {
+    pub command: PollPolicy,
+    pub read: PollPolicy,
+    pub write: PollPolicy,
+    pub erase: PollPolicy,
+    pub sanitize: PollPolicy,
+}
+
+impl PollPolicies {
+    pub fn get(&self, operation: PollOperation) -> PollPolicy {
+        match operation {
+            PollOperation::Command => self.command,
+            PollOperation::Read => self.read,
+            PollOperation::Write => self.write,
+            PollOperation::Erase => self.erase,
+            PollOperation::Sanitize => self.sanitize,
+        }
+    }
+
+    pub fn set(&mut self, operation: PollOperation, policy: PollPolicy) {
+        match operation {
+            PollOperation::Command => self.command = policy,
+            PollOperation::Read => self.read = policy,
+            PollOperation::Write => self.write = policy,
+            PollOperation::Erase => self.erase = policy,
+            PollOperation::Sanitize => self.sanitize = policy,
+        }
+    }
+}
+
+impl Default for PollPolicies {
+    fn default() -> Self {
+        Self {
+            command: PollPolicy::DEFAULT,
+            read: PollPolicy::FAST,
+            write: PollPolicy::LONG,
+            erase: PollPolicy::LONG,
+            sanitize: PollPolicy {
+                timeout: Duration::from_secs(600),
+                ..PollPolicy::LONG
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records requested delays
+    struct RecordingDelay(Vec<u32>);
+
+    /// Advances by a fixed step on every reading
+    struct StepClock {
+        now: Duration,
+        step: Duration,
+    }
+
+    impl PollClock for StepClock {
+        fn now(&mut self) -> Duration {
+            self.now += self.step;
+            self.now
+        }
+    }
+
+    impl DelayTrait for RecordingDelay {
+        fn delay_ns(&mut self, ns: u32) {
+            self.0.push(ns / 1000);
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(PollMatch::Exact(0x21).matches(0x21));
+        assert!(!PollMatch::Exact(0x21).matches(0x20));
+        assert!(PollMatch::Mask { mask: 0x3, value: 0x1 }.matches(0xF1));
+        assert!(!PollMatch::Mask { mask: 0x3, value: 0x1 }.matches(0xF3));
+        assert!(PollMatch::AnyBit(0x8002).matches(0x8000));
+        assert!(!PollMatch::AnyBit(0x8002).matches(0x1));
+    }
+
+    #[test]
+    fn test_backoff_and_timeout() {
+        let policy = PollPolicy {
+            timeout: Duration::from_micros(1000),
+            interval: Duration::from_micros(100),
+            backoff: 2,
+            max_interval: Duration::from_micros(300),
+        };
+        let mut delay = RecordingDelay(Vec::new());
+        let mut reads = 0;
+        let err = policy
+            .poll(Register::InterruptStatus, PollMatch::AnyBit(1), &mut delay, &mut NoClock, || {
+                reads += 1;
+                Ok(0x8000)
+            })
+            .unwrap_err();
+
+        assert_eq!(delay.0, [100, 200, 300, 300, 100]);
+        assert_eq!(reads, 6);
+        assert!(matches!(
+            err.into_kind(),
+            ErrorKind::PollTimeout {
+                register: Register::InterruptStatus,
+                last_value: 0x8000,
+                elapsed,
+            } if elapsed == Duration::from_micros(1000)
+        ));
+    }
+
+    #[test]
+    fn test_poll_returns_matching_value() {
+        let mut values = [0x0, 0x0, 0x3].into_iter();
+        let mut delay = RecordingDelay(Vec::new());
+        let value = PollPolicy::DEFAULT
+            .poll(Register::InterruptStatus, PollMatch::AnyBit(0x2), &mut delay, &mut NoClock, || {
+                Ok(values.next().unwrap())
+            })
+            .unwrap();
+        assert_eq!(value, 0x3);
+        assert_eq!(delay.0, [10_000, 10_000]);
+    }
+
+    #[test]
+    fn test_default_poll_count() {
+        let mut delay = RecordingDelay(Vec::new());
+        let mut reads = 0;
+        PollPolicy::DEFAULT
+            .poll(Register::InterruptStatus, PollMatch::AnyBit(1), &mut delay, &mut NoClock, || {
+                reads += 1;
+                Ok(0)
+            })
+            .unwrap_err();
+        assert_eq!(reads, 10);
+        assert_eq!(delay.0, [10_000; 9]);
+    }
+
+    #[test]
+    fn test_clock_counts_bus_time() {
+        // 15ms pass per poll: the 10ms delay plus 5ms on the bus
+        let mut clock = StepClock {
+            now: Duration::ZERO,
+            step: Duration::from_millis(15),
+        };
+        let mut delay = RecordingDelay(Vec::new());
+        let mut reads = 0;
+        let err = PollPolicy::DEFAULT
+            .poll(Register::InterruptStatus, PollMatch::AnyBit(1), &mut delay, &mut clock, || {
+                reads += 1;
+                Ok(0)
+            })
+            .unwrap_err();
+
+        assert_eq!(reads, 6);
+        assert_eq!(delay.0, [10_000; 5]);
+        assert!(matches!(
+            err.into_kind(),
+            ErrorKind::PollTimeout { elapsed, .. } if elapsed == Duration::from_millis(90)
+        ));
+    }
+}
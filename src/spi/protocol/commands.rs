@@ -24,10 +24,14 @@ impl Command {
 }
 
 /// eMMC SPI Controller Register addresses (8 bits)
+///
+/// The low registers line up with the SD Host Controller register map
+/// (SPI address = SDHCI offset / 4), see [`super::registers`] for field layouts.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum Register {
-    /// Register 0x01
+    /// Register 0x01 (SDHCI Block Size / Block Count)
     Reg_01 = 0x01,
 
     /// Argument register - buffer/FIFO config
@@ -48,7 +52,7 @@ pub enum Register {
     /// Present State register
     PresentState = 0x09,
 
-    /// Register 0x0A
+    /// Register 0x0A (SDHCI Host Control 1 / Power / Block Gap / Wakeup)
     Reg_0A = 0x0A,
 
     /// Command register (for issuing commands to eMMC) - also known as StatusConfig
@@ -63,7 +67,7 @@ pub enum Register {
     /// Configuration register 2
     Config2 = 0x0E,
 
-    /// Register 0x0F
+    /// Register 0x0F (SDHCI Auto CMD Error Status / Host Control 2)
     Reg_0F = 0x0F,
 
     /// Initialization command register
@@ -72,8 +76,21 @@ pub enum Register {
     /// Register 0x88
     XipOutputDelay = 0x88,
 
-    XipDataFirst = 0xC0,
-    XipDataLast = 0xCD,
+    /// XIP data window, holds the SMC fuses after [`Register::InitCommand`]
+    XipData0 = 0xC0,
+    XipData1 = 0xC1,
+    XipData2 = 0xC2,
+    XipData3 = 0xC3,
+    XipData4 = 0xC4,
+    XipData5 = 0xC5,
+    XipData6 = 0xC6,
+    XipData7 = 0xC7,
+    XipData8 = 0xC8,
+    XipData9 = 0xC9,
+    XipData10 = 0xCA,
+    XipData11 = 0xCB,
+    XipData12 = 0xCC,
+    XipData13 = 0xCD,
 }
 
 impl From<Register> for u8 {
@@ -95,23 +112,64 @@ impl Register {
 
     /// Create from raw address value
     pub fn from_address(addr: u8) -> Option<Self> {
-        match addr {
-            0x02 => Some(Self::Argument),
-            0x03 => Some(Self::CommandAndTransferMode),
-            0x04 => Some(Self::Response0And1),
-            0x05 => Some(Self::Response2And3),
-            0x06 => Some(Self::Response4And5),
-            0x07 => Some(Self::Response6And7),
-            0x08 => Some(Self::DataFifo),
-            0x09 => Some(Self::PresentState),
-            0x0B => Some(Self::Command),
-            0x0C => Some(Self::InterruptStatus),
-            0x0D => Some(Self::Config1),
-            0x0E => Some(Self::Config2),
-            0x44 => Some(Self::InitCommand),
-            _ => None,
-        }
+        Self::ALL.into_iter().find(|register| register.address() == addr)
     }
+
+    /// XIP data window, in address order
+    pub const XIP_DATA: [Register; 14] = [
+        Self::XipData0,
+        Self::XipData1,
+        Self::XipData2,
+        Self::XipData3,
+        Self::XipData4,
+        Self::XipData5,
+        Self::XipData6,
+        Self::XipData7,
+        Self::XipData8,
+        Self::XipData9,
+        Self::XipData10,
+        Self::XipData11,
+        Self::XipData12,
+        Self::XipData13,
+    ];
+
+    pub const XIP_DATA_FIRST: Register = Self::XipData0;
+    pub const XIP_DATA_LAST: Register = Self::XipData13;
+
+    /// Every known register, in address order
+    pub const ALL: [Register; 31] = [
+        Self::Reg_01,
+        Self::Argument,
+        Self::CommandAndTransferMode,
+        Self::Response0And1,
+        Self::Response2And3,
+        Self::Response4And5,
+        Self::Response6And7,
+        Self::DataFifo,
+        Self::PresentState,
+        Self::Reg_0A,
+        Self::Command,
+        Self::InterruptStatus,
+        Self::Config1,
+        Self::Config2,
+        Self::Reg_0F,
+        Self::InitCommand,
+        Self::XipOutputDelay,
+        Self::XipData0,
+        Self::XipData1,
+        Self::XipData2,
+        Self::XipData3,
+        Self::XipData4,
+        Self::XipData5,
+        Self::XipData6,
+        Self::XipData7,
+        Self::XipData8,
+        Self::XipData9,
+        Self::XipData10,
+        Self::XipData11,
+        Self::XipData12,
+        Self::XipData13,
+    ];
 }
 
 /// Data size for register operations
@@ -193,18 +251,23 @@ impl ErrorFlags {
 }
 
 /// Status codes
+///
+/// Raw [`InterruptStatus`](super::registers::InterruptStatus) values seen in the protocol trace.
 pub mod status {
+    use super::super::registers::InterruptStatus;
+
     /// Data ready status - indicates 512 bytes are ready to read from DataFifo
-    pub const DATA_READY: u32 = 0x00000020;
+    pub const DATA_READY: u32 = InterruptStatus::BUFFER_READ_READY.mask();
 
     /// Command accepted status - indicates command was accepted and processing started
-    pub const CMD_ACCEPTED: u32 = 0x00000021;
+    pub const CMD_ACCEPTED: u32 =
+        InterruptStatus::COMMAND_COMPLETE.mask() | InterruptStatus::BUFFER_READ_READY.mask();
 
     /// Transfer complete status - indicates block transfer is finished
-    pub const TRANSFER_COMPLETE: u32 = 0x00000002;
+    pub const TRANSFER_COMPLETE: u32 = InterruptStatus::TRANSFER_COMPLETE.mask();
 
     /// Command/Busy status - written to initiate operations
-    pub const CMD_BUSY: u32 = 0x00000001;
+    pub const CMD_BUSY: u32 = InterruptStatus::COMMAND_COMPLETE.mask();
 
     /// Status clear/reset value - written to clear status after acknowledgement
     pub const STATUS_CLEAR: u32 = 0xFFFFFFFF;
@@ -214,6 +277,31 @@ pub mod status {
 pub mod transfer_config {
     /// Standard transfer configuration for 512-byte page reads
     pub const PAGE_READ: u32 = 0x113A0010;
+
+    /// CMD7 SELECT/DESELECT_CARD with RCA 0, deselects without a response
+    ///
+    /// Not traced: [`SELECT_CARD`] with the response flags cleared.
+    pub const DESELECT_CARD: u32 = 0x07000000;
+
+    /// CMD7 SELECT/DESELECT_CARD, R1
+    ///
+    /// Traced: written by the init sequence after CMD3.
+    pub const SELECT_CARD: u32 = 0x071A0000;
+
+    /// CMD9 SEND_CSD, R2 (card must be in standby)
+    ///
+    /// Not traced: R2 flags (`0x09`) as in the traced CMD2 `0x02090000`.
+    pub const SEND_CSD: u32 = 0x09090000;
+
+    /// CMD10 SEND_CID, R2 (card must be in standby)
+    ///
+    /// Not traced: R2 flags (`0x09`) as in the traced CMD2 `0x02090000`.
+    pub const SEND_CID: u32 = 0x0A090000;
+
+    /// CMD13 SEND_STATUS, R1
+    ///
+    /// Not traced: R1 flags (`0x1A`) as in the traced CMD3/CMD7 `0x031A0000`.
+    pub const SEND_STATUS: u32 = 0x0D1A0000;
 }
 
 #[cfg(test)]
@@ -238,9 +326,32 @@ mod tests {
     fn test_register_from_address() {
         assert_eq!(Register::from_address(0x02), Some(Register::Argument));
         assert_eq!(Register::from_address(0x44), Some(Register::InitCommand));
+        assert_eq!(Register::from_address(0x01), Some(Register::Reg_01));
+        assert_eq!(Register::from_address(0x0A), Some(Register::Reg_0A));
+        assert_eq!(Register::from_address(0x0F), Some(Register::Reg_0F));
+        assert_eq!(Register::from_address(0xC5), Some(Register::XipData5));
+        assert_eq!(Register::from_address(0xCD), Some(Register::XIP_DATA_LAST));
+        assert_eq!(Register::from_address(0xCE), None);
         assert_eq!(Register::from_address(0xFF), None);
     }
 
+    #[test]
+    fn test_register_list() {
+        for register in Register::ALL {
+            assert_eq!(Register::from_address(register.address()), Some(register));
+        }
+        assert!(Register::ALL.windows(2).all(|w| w[0].address() < w[1].address()));
+        assert!(Register::XIP_DATA.windows(2).all(|w| w[0].address() + 1 == w[1].address()));
+    }
+
+    #[test]
+    fn test_status_codes() {
+        assert_eq!(status::DATA_READY, 0x20);
+        assert_eq!(status::CMD_ACCEPTED, 0x21);
+        assert_eq!(status::TRANSFER_COMPLETE, 0x02);
+        assert_eq!(status::CMD_BUSY, 0x01);
+    }
+
     #[test]
     fn test_data_sizes() {
         assert_eq!(DataSize::Register.bytes(), 4);
@@ -3,4 +3,5 @@
 //! This module defines the protocol structures and operations without
 //! depending on any specific hardware backend (FTDI, embedded-hal, etc.)
 pub mod commands;
+pub mod registers;
 pub mod transaction;
\ No newline at end of file
@@ -0,0 +1,381 @@
+/// Typed field-level views of the eMMC SPI controller registers
+///
+/// The controller behaves like an SD Host Controller (SDHCI) behind the SPI
+/// bridge, so the layouts below follow the SDHCI specification. They match
+/// every value seen in the protocol traces (e.g. `0x113A0010` is CMD17 with a
+/// 48-bit CRC-checked response and a single block read).
+use crate::prelude::*;
+use super::commands::Register;
+
+/// Access semantics of a register field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Read-only (RO), writes are ignored
+    ReadOnly,
+    /// Read/write (RW)
+    ReadWrite,
+    /// Write 1 to clear (W1C)
+    WriteOneToClear,
+}
+
+/// A bitfield inside a 32-bit register
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    pub name: &'static str,
+    pub offset: u8,
+    pub width: u8,
+    pub access: Access,
+}
+
+impl Field {
+    pub const fn new(name: &'static str, offset: u8, width: u8, access: Access) -> Self {
+        Self {
+            name,
+            offset,
+            width,
+            access,
+        }
+    }
+
+    /// Mask of the field within the register
+    pub const fn mask(&self) -> u32 {
+        (u32::MAX >> (32 - self.width as u32)) << self.offset
+    }
+
+    /// Extract the field value from a raw register value
+    pub const fn extract(&self, bits: u32) -> u32 {
+        (bits & self.mask()) >> self.offset
+    }
+
+    /// Replace the field value in a raw register value
+    pub const fn insert(&self, bits: u32, value: u32) -> u32 {
+        (bits & !self.mask()) | ((value << self.offset) & self.mask())
+    }
+
+    pub const fn is_writable(&self) -> bool {
+        !matches!(self.access, Access::ReadOnly)
+    }
+}
+
+/// Conversion of raw field bits into the getter's return type
+trait FieldValue {
+    fn from_raw(raw: u32) -> Self;
+}
+
+impl FieldValue for bool {
+    fn from_raw(raw: u32) -> Self {
+        raw != 0
+    }
+}
+
+impl FieldValue for u8 {
+    fn from_raw(raw: u32) -> Self {
+        raw as u8
+    }
+}
+
+impl FieldValue for u16 {
+    fn from_raw(raw: u32) -> Self {
+        raw as u16
+    }
+}
+
+/// Define a register wrapper with named fields
+///
+/// Each field is declared as `CONST_NAME / getter: Type = offset, width, Access;`.
+macro_rules! register_fields {
+    (
+        $(#[$meta:meta])*
+        $name:ident [$($register:ident),+] {
+            $(
+                $(#[$fmeta:meta])*
+                $field:ident / $getter:ident : $ty:ty = $offset:literal, $width:literal, $access:ident;
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, PartialEq, Eq, Default)]
+        pub struct $name(pub u32);
+
+        impl $name {
+            /// Registers using this layout
+            pub const REGISTERS: &'static [Register] = &[$(Register::$register),+];
+
+            $(
+                $(#[$fmeta])*
+                pub const $field: Field =
+                    Field::new(stringify!($getter), $offset, $width, Access::$access);
+            )*
+
+            /// All fields, in bit order
+            pub const FIELDS: &'static [Field] = &[$(Self::$field),*];
+
+            pub const fn empty() -> Self {
+                Self(0)
+            }
+
+            pub const fn from_bits(bits: u32) -> Self {
+                Self(bits)
+            }
+
+            pub const fn bits(self) -> u32 {
+                self.0
+            }
+
+            /// Raw value of a field
+            pub const fn get(self, field: Field) -> u32 {
+                field.extract(self.0)
+            }
+
+            /// Copy with a field replaced
+            pub const fn with(self, field: Field, value: u32) -> Self {
+                Self(field.insert(self.0, value))
+            }
+
+            /// Value to write so that only writable fields are changed
+            pub const fn writable_bits(self) -> u32 {
+                let mut mask = 0;
+                let mut i = 0;
+                while i < Self::FIELDS.len() {
+                    if Self::FIELDS[i].is_writable() {
+                        mask |= Self::FIELDS[i].mask();
+                    }
+                    i += 1;
+                }
+                self.0 & mask
+            }
+
+            $(
+                $(#[$fmeta])*
+                pub fn $getter(self) -> $ty {
+                    FieldValue::from_raw(Self::$field.extract(self.0))
+                }
+            )*
+        }
+
+        impl From<u32> for $name {
+            fn from(bits: u32) -> Self {
+                Self(bits)
+            }
+        }
+
+        impl From<$name> for u32 {
+            fn from(val: $name) -> Self {
+                val.0
+            }
+        }
+
+        /// Shows the raw value and every non-zero field
+        impl core::fmt::Debug for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                let mut s = f.debug_struct(stringify!($name));
+                s.field("bits", &format_args!("{:#010X}", self.0));
+                for field in Self::FIELDS {
+                    let value = field.extract(self.0);
+                    if value == 0 {
+                        continue;
+                    }
+                    if field.width == 1 {
+                        s.field(field.name, &true);
+                    } else {
+                        s.field(field.name, &format_args!("{value:#X}"));
+                    }
+                }
+                s.finish()
+            }
+        }
+    };
+}
+
+register_fields! {
+    /// Normal and error interrupt status (SDHCI 0x30)
+    InterruptStatus [InterruptStatus] {
+        COMMAND_COMPLETE / command_complete: bool = 0, 1, WriteOneToClear;
+        TRANSFER_COMPLETE / transfer_complete: bool = 1, 1, WriteOneToClear;
+        BLOCK_GAP_EVENT / block_gap_event: bool = 2, 1, WriteOneToClear;
+        DMA_INTERRUPT / dma_interrupt: bool = 3, 1, WriteOneToClear;
+        BUFFER_WRITE_READY / buffer_write_ready: bool = 4, 1, WriteOneToClear;
+        /// A block is waiting in [`Register::DataFifo`]
+        BUFFER_READ_READY / buffer_read_ready: bool = 5, 1, WriteOneToClear;
+        CARD_INSERTION / card_insertion: bool = 6, 1, WriteOneToClear;
+        CARD_REMOVAL / card_removal: bool = 7, 1, WriteOneToClear;
+        CARD_INTERRUPT / card_interrupt: bool = 8, 1, ReadOnly;
+        /// Summary of bits 16..
+        ERROR_INTERRUPT / error_interrupt: bool = 15, 1, ReadOnly;
+        COMMAND_TIMEOUT_ERROR / command_timeout_error: bool = 16, 1, WriteOneToClear;
+        COMMAND_CRC_ERROR / command_crc_error: bool = 17, 1, WriteOneToClear;
+        COMMAND_END_BIT_ERROR / command_end_bit_error: bool = 18, 1, WriteOneToClear;
+        COMMAND_INDEX_ERROR / command_index_error: bool = 19, 1, WriteOneToClear;
+        DATA_TIMEOUT_ERROR / data_timeout_error: bool = 20, 1, WriteOneToClear;
+        DATA_CRC_ERROR / data_crc_error: bool = 21, 1, WriteOneToClear;
+        DATA_END_BIT_ERROR / data_end_bit_error: bool = 22, 1, WriteOneToClear;
+        CURRENT_LIMIT_ERROR / current_limit_error: bool = 23, 1, WriteOneToClear;
+        AUTO_CMD_ERROR / auto_cmd_error: bool = 24, 1, WriteOneToClear;
+        ADMA_ERROR / adma_error: bool = 25, 1, WriteOneToClear;
+    }
+}
+
+register_fields! {
+    /// Interrupt status enable ([`Register::Config1`], SDHCI 0x34) and
+    /// signal enable ([`Register::Config2`], SDHCI 0x38)
+    ///
+    /// Same bit positions as [`InterruptStatus`].
+    InterruptEnable [Config1, Config2] {
+        COMMAND_COMPLETE / command_complete: bool = 0, 1, ReadWrite;
+        TRANSFER_COMPLETE / transfer_complete: bool = 1, 1, ReadWrite;
+        BLOCK_GAP_EVENT / block_gap_event: bool = 2, 1, ReadWrite;
+        DMA_INTERRUPT / dma_interrupt: bool = 3, 1, ReadWrite;
+        BUFFER_WRITE_READY / buffer_write_ready: bool = 4, 1, ReadWrite;
+        BUFFER_READ_READY / buffer_read_ready: bool = 5, 1, ReadWrite;
+        CARD_INSERTION / card_insertion: bool = 6, 1, ReadWrite;
+        CARD_REMOVAL / card_removal: bool = 7, 1, ReadWrite;
+        CARD_INTERRUPT / card_interrupt: bool = 8, 1, ReadWrite;
+        COMMAND_TIMEOUT_ERROR / command_timeout_error: bool = 16, 1, ReadWrite;
+        COMMAND_CRC_ERROR / command_crc_error: bool = 17, 1, ReadWrite;
+        COMMAND_END_BIT_ERROR / command_end_bit_error: bool = 18, 1, ReadWrite;
+        COMMAND_INDEX_ERROR / command_index_error: bool = 19, 1, ReadWrite;
+        DATA_TIMEOUT_ERROR / data_timeout_error: bool = 20, 1, ReadWrite;
+        DATA_CRC_ERROR / data_crc_error: bool = 21, 1, ReadWrite;
+        DATA_END_BIT_ERROR / data_end_bit_error: bool = 22, 1, ReadWrite;
+        CURRENT_LIMIT_ERROR / current_limit_error: bool = 23, 1, ReadWrite;
+        AUTO_CMD_ERROR / auto_cmd_error: bool = 24, 1, ReadWrite;
+        ADMA_ERROR / adma_error: bool = 25, 1, ReadWrite;
+        VENDOR_ERROR / vendor_error: u8 = 28, 4, ReadWrite;
+    }
+}
+
+register_fields! {
+    /// Present state (SDHCI 0x24)
+    PresentState [PresentState] {
+        COMMAND_INHIBIT / command_inhibit: bool = 0, 1, ReadOnly;
+        DATA_INHIBIT / data_inhibit: bool = 1, 1, ReadOnly;
+        DATA_LINE_ACTIVE / data_line_active: bool = 2, 1, ReadOnly;
+        WRITE_TRANSFER_ACTIVE / write_transfer_active: bool = 8, 1, ReadOnly;
+        READ_TRANSFER_ACTIVE / read_transfer_active: bool = 9, 1, ReadOnly;
+        BUFFER_WRITE_ENABLE / buffer_write_enable: bool = 10, 1, ReadOnly;
+        BUFFER_READ_ENABLE / buffer_read_enable: bool = 11, 1, ReadOnly;
+        CARD_INSERTED / card_inserted: bool = 16, 1, ReadOnly;
+        CARD_STATE_STABLE / card_state_stable: bool = 17, 1, ReadOnly;
+        CARD_DETECT_LEVEL / card_detect_level: bool = 18, 1, ReadOnly;
+        WRITE_PROTECT_LEVEL / write_protect_level: bool = 19, 1, ReadOnly;
+        /// DAT[3:0] line levels
+        DATA_LEVEL / data_level: u8 = 20, 4, ReadOnly;
+        COMMAND_LEVEL / command_level: bool = 24, 1, ReadOnly;
+    }
+}
+
+register_fields! {
+    /// Clock control, timeout control and software reset
+    /// ([`Register::Command`], also known as StatusConfig, SDHCI 0x2C)
+    ///
+    /// The init sequence writes `0xE0047`: internal and SD clock enabled,
+    /// data timeout `0xE`.
+    ClockControl [Command] {
+        INTERNAL_CLOCK_ENABLE / internal_clock_enable: bool = 0, 1, ReadWrite;
+        INTERNAL_CLOCK_STABLE / internal_clock_stable: bool = 1, 1, ReadOnly;
+        SD_CLOCK_ENABLE / sd_clock_enable: bool = 2, 1, ReadWrite;
+        CLOCK_GENERATOR_SELECT / clock_generator_select: bool = 5, 1, ReadWrite;
+        /// Upper two bits of the 10-bit clock divider
+        DIVIDER_UPPER / divider_upper: u8 = 6, 2, ReadWrite;
+        DIVIDER / divider: u8 = 8, 8, ReadWrite;
+        DATA_TIMEOUT / data_timeout: u8 = 16, 4, ReadWrite;
+        SOFTWARE_RESET_ALL / software_reset_all: bool = 24, 1, ReadWrite;
+        SOFTWARE_RESET_COMMAND / software_reset_command: bool = 25, 1, ReadWrite;
+        SOFTWARE_RESET_DATA / software_reset_data: bool = 26, 1, ReadWrite;
+    }
+}
+
+register_fields! {
+    /// Command and transfer mode (SDHCI 0x0C), writing it issues the command
+    CommandAndTransferMode [CommandAndTransferMode] {
+        DMA_ENABLE / dma_enable: bool = 0, 1, ReadWrite;
+        BLOCK_COUNT_ENABLE / block_count_enable: bool = 1, 1, ReadWrite;
+        /// 1 = Auto CMD12, 2 = Auto CMD23
+        AUTO_CMD_ENABLE / auto_cmd_enable: u8 = 2, 2, ReadWrite;
+        /// Set for card to host transfers
+        DATA_DIRECTION_READ / data_direction_read: bool = 4, 1, ReadWrite;
+        MULTI_BLOCK / multi_block: bool = 5, 1, ReadWrite;
+        /// 0 = none, 1 = 136 bit, 2 = 48 bit, 3 = 48 bit with busy
+        RESPONSE_TYPE / response_type: u8 = 16, 2, ReadWrite;
+        CRC_CHECK / crc_check: bool = 19, 1, ReadWrite;
+        INDEX_CHECK / index_check: bool = 20, 1, ReadWrite;
+        DATA_PRESENT / data_present: bool = 21, 1, ReadWrite;
+        COMMAND_TYPE / command_type: u8 = 22, 2, ReadWrite;
+        COMMAND_INDEX / command_index: u8 = 24, 6, ReadWrite;
+    }
+}
+
+register_fields! {
+    /// XIP output delay
+    ///
+    /// Vendor register, the layout is inferred from the `0x70001` the init
+    /// sequence writes and reads back.
+    XipOutputDelay [XipOutputDelay] {
+        ENABLE / enable: bool = 0, 1, ReadWrite;
+        DELAY / delay: u8 = 16, 4, ReadWrite;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::commands::{status, transfer_config};
+
+    #[test]
+    fn test_field_access() {
+        let field = CommandAndTransferMode::COMMAND_INDEX;
+        assert_eq!(field.mask(), 0x3F00_0000);
+        assert_eq!(field.insert(0xFFFF_FFFF, 0), 0xC0FF_FFFF);
+        assert_eq!(field.extract(0x113A_0010), 17);
+        assert!(!InterruptStatus::ERROR_INTERRUPT.is_writable());
+    }
+
+    #[test]
+    fn test_page_read_command() {
+        let cmd = CommandAndTransferMode::from_bits(transfer_config::PAGE_READ);
+        assert_eq!(cmd.command_index(), 17);
+        assert_eq!(cmd.response_type(), 2);
+        assert!(cmd.crc_check());
+        assert!(cmd.index_check());
+        assert!(cmd.data_present());
+        assert!(cmd.data_direction_read());
+        assert!(!cmd.multi_block());
+
+        let built = CommandAndTransferMode::empty()
+            .with(CommandAndTransferMode::COMMAND_INDEX, 17)
+            .with(CommandAndTransferMode::RESPONSE_TYPE, 2)
+            .with(CommandAndTransferMode::CRC_CHECK, 1)
+            .with(CommandAndTransferMode::INDEX_CHECK, 1)
+            .with(CommandAndTransferMode::DATA_PRESENT, 1)
+            .with(CommandAndTransferMode::DATA_DIRECTION_READ, 1);
+        assert_eq!(built.bits(), transfer_config::PAGE_READ);
+    }
+
+    #[test]
+    fn test_status_values() {
+        let accepted = InterruptStatus::from_bits(status::CMD_ACCEPTED);
+        assert!(accepted.command_complete());
+        assert!(accepted.buffer_read_ready());
+        assert!(InterruptStatus::from_bits(status::TRANSFER_COMPLETE).transfer_complete());
+
+        let clock = ClockControl::from_bits(0xE0047);
+        assert!(clock.internal_clock_enable());
+        assert!(clock.sd_clock_enable());
+        assert_eq!(clock.data_timeout(), 0xE);
+
+        // Read-only bits are dropped from write values
+        assert_eq!(ClockControl::from_bits(0x3).writable_bits(), 0x1);
+    }
+
+    #[test]
+    fn test_debug_decodes_fields() {
+        let text = format!("{:?}", InterruptStatus::from_bits(0x21));
+        assert_eq!(
+            text,
+            "InterruptStatus { bits: 0x00000021, command_complete: true, buffer_read_ready: true }"
+        );
+
+        let text = format!("{:?}", XipOutputDelay::from_bits(0x70001));
+        assert_eq!(text, "XipOutputDelay { bits: 0x00070001, enable: true, delay: 0x7 }");
+    }
+}
@@ -0,0 +1,162 @@
+/// Controller register scan for reverse engineering
+///
+/// A scan reads every address of the controller and probes which bits are
+/// writable (write inverted value, read back, restore). The report prints one
+/// line per address so reports from different console revisions can be
+/// compared with a plain text diff, or with [`RegisterScan::diff`].
+use crate::prelude::*;
+use super::protocol::commands::Register;
+
+/// Addresses that are never written during a scan
+///
+/// * `CommandAndTransferMode` - writing issues a command to the eMMC
+/// * `DataFifo` - reading pops data from the FIFO
+/// * `Reg_0A` - SDHCI host control 1 / power control
+/// * `Command` - clock control and software reset
+/// * `InterruptStatus` - write 1 to clear
+/// * `InitCommand` - bridge initialization
+pub const DEFAULT_SCAN_DENYLIST: [u8; 6] = [
+    Register::CommandAndTransferMode as u8,
+    Register::DataFifo as u8,
+    Register::Reg_0A as u8,
+    Register::Command as u8,
+    Register::InterruptStatus as u8,
+    Register::InitCommand as u8,
+];
+
+/// Addresses that are not even read, because reading has side effects
+pub const SCAN_READ_DENYLIST: [u8; 1] = [Register::DataFifo as u8];
+
+/// Scan result of a single address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ScanEntry {
+    pub address: u8,
+    /// Value read before probing, `None` if the address was not read
+    pub value: Option<u32>,
+    /// Bits that changed on write, `None` if the address was not probed
+    pub writable: Option<u32>,
+    /// Whether the original value was read back after restoring it
+    pub restored: bool,
+}
+
+impl ScanEntry {
+    /// Known register at this address
+    pub fn register(&self) -> Option<Register> {
+        Register::from_address(self.address)
+    }
+}
+
+impl core::fmt::Display for ScanEntry {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:#04x}  ", self.address)?;
+        match self.value {
+            Some(value) => write!(f, "{value:#010x}  ")?,
+            None => write!(f, "{:<10}  ", "skipped")?,
+        }
+        match self.writable {
+            Some(writable) => write!(f, "{writable:#010x}")?,
+            None => write!(f, "{:<10}", "skipped")?,
+        }
+        if self.writable.is_some() && !self.restored {
+            write!(f, "  NOT RESTORED")?;
+        }
+        if let Some(register) = self.register() {
+            write!(f, "  {register:?}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Scan report covering the whole 8-bit address space
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterScan {
+    pub entries: [ScanEntry; 256],
+}
+
+impl RegisterScan {
+    pub fn new() -> Self {
+        let mut entries = [ScanEntry::default(); 256];
+        for (address, entry) in entries.iter_mut().enumerate() {
+            entry.address = address as u8;
+        }
+        Self { entries }
+    }
+
+    /// Addresses that returned a non-zero value or have writable bits
+    pub fn active(&self) -> impl Iterator<Item = &ScanEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.value.is_some_and(|v| v != 0) || e.writable.is_some_and(|w| w != 0))
+    }
+
+    /// Entries that differ from another scan, as `(self, other)` pairs
+    pub fn diff<'a>(
+        &'a self,
+        other: &'a RegisterScan,
+    ) -> impl Iterator<Item = (&'a ScanEntry, &'a ScanEntry)> {
+        self.entries
+            .iter()
+            .zip(other.entries.iter())
+            .filter(|(a, b)| a != b)
+    }
+}
+
+impl Default for RegisterScan {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl core::fmt::Display for RegisterScan {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "addr  value       writable    register")?;
+        for entry in &self.entries {
+            writeln!(f, "{entry}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_format() {
+        let entry = ScanEntry {
+            address: 0x02,
+            value: Some(0x1234),
+            writable: Some(0xFFFF_FFFF),
+            restored: true,
+        };
+        assert_eq!(format!("{entry}"), "0x02  0x00001234  0xffffffff  Argument");
+
+        let entry = ScanEntry {
+            address: 0x08,
+            value: None,
+            writable: None,
+            restored: false,
+        };
+        assert_eq!(format!("{entry}"), "0x08  skipped     skipped     DataFifo");
+
+        let entry = ScanEntry {
+            address: 0x50,
+            value: Some(0),
+            writable: Some(0x1),
+            restored: false,
+        };
+        assert_eq!(format!("{entry}"), "0x50  0x00000000  0x00000001  NOT RESTORED");
+    }
+
+    #[test]
+    fn test_diff() {
+        let a = RegisterScan::new();
+        let mut b = RegisterScan::new();
+        b.entries[0x0A].value = Some(0x800020);
+
+        let diff: Vec<_> = a.diff(&b).collect();
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].1.register(), Some(Register::Reg_0A));
+        assert_eq!(b.active().count(), 1);
+    }
+}
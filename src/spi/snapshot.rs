@@ -0,0 +1,229 @@
+/// Controller register and card state snapshots
+///
+/// A [`RegisterSnapshot`] captures every known controller register and,
+/// optionally, the card's CID, CSD and status. Snapshots can be
+/// printed, compared with [`RegisterSnapshot::diff`] and, with the `serde`
+/// feature, stored as JSON for bug reports.
+use crate::prelude::*;
+use super::protocol::commands::{MmcState, Register};
+use super::protocol::registers::{
+    ClockControl, CommandAndTransferMode, InterruptEnable, InterruptStatus, PresentState,
+    XipOutputDelay,
+};
+
+/// Value of a single controller register
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegisterReading {
+    pub register: Register,
+    /// `None` for registers that are not read because reading has side effects
+    pub value: Option<u32>,
+}
+
+impl core::fmt::Display for RegisterReading {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let Some(value) = self.value else {
+            return write!(f, "{:?}: not read", self.register);
+        };
+
+        match self.register {
+            Register::InterruptStatus => write!(f, "{:?}", InterruptStatus(value)),
+            Register::PresentState => write!(f, "{:?}", PresentState(value)),
+            Register::Command => write!(f, "{:?}", ClockControl(value)),
+            Register::CommandAndTransferMode => write!(f, "{:?}", CommandAndTransferMode(value)),
+            Register::Config1 | Register::Config2 => {
+                write!(f, "{:?}: {:?}", self.register, InterruptEnable(value))
+            }
+            Register::XipOutputDelay => write!(f, "{:?}", XipOutputDelay(value)),
+            register => write!(f, "{register:?}: {value:#010X}"),
+        }
+    }
+}
+
+/// Card registers, as raw response words
+///
+/// R2 responses (CID, CSD) are stored as read from `Response0And1` to
+/// `Response6And7`, i.e. bits 127:8 of the register with the CRC stripped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CardState {
+    pub cid: [u32; 4],
+    pub csd: [u32; 4],
+    /// R1 card status from CMD13
+    pub status: u32,
+}
+
+impl CardState {
+    /// Current state from the card status (bits 12:9)
+    pub fn state(&self) -> Option<MmcState> {
+        MmcState::from_bits((self.status >> 9) as u8)
+    }
+}
+
+/// Number of registers captured by a snapshot
+pub const SNAPSHOT_REGISTERS: usize = Register::ALL.len();
+
+/// Controller registers and card state at one point in time
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegisterSnapshot {
+    /// Readings in [`Register::ALL`] order
+    pub registers: [RegisterReading; SNAPSHOT_REGISTERS],
+    /// `None` unless card registers were requested and the card was initialized
+    pub card: Option<CardState>,
+}
+
+/// Location of a value inside a snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotField {
+    Register(Register),
+    Cid(usize),
+    Csd(usize),
+    CardStatus,
+    /// Card state present in only one of the snapshots
+    Card,
+}
+
+/// A value that differs between two snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    pub field: SnapshotField,
+    pub old: Option<u32>,
+    pub new: Option<u32>,
+}
+
+impl core::fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.field {
+            SnapshotField::Register(register) => write!(f, "{register:?}")?,
+            SnapshotField::Cid(i) => write!(f, "CID[{i}]")?,
+            SnapshotField::Csd(i) => write!(f, "CSD[{i}]")?,
+            SnapshotField::CardStatus => write!(f, "CardStatus")?,
+            SnapshotField::Card => write!(f, "Card")?,
+        }
+        for value in [self.old, self.new] {
+            match value {
+                Some(value) => write!(f, " {value:#010X}")?,
+                None => write!(f, " -")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RegisterSnapshot {
+    /// Value of a register, if it was read
+    pub fn get(&self, register: Register) -> Option<u32> {
+        self.registers
+            .iter()
+            .find(|reading| reading.register == register)
+            .and_then(|reading| reading.value)
+    }
+
+    /// Values that differ from `other` (`old` is self, `new` is other)
+    pub fn diff<'a>(&'a self, other: &'a RegisterSnapshot) -> impl Iterator<Item = SnapshotDiff> + 'a {
+        let registers = self
+            .registers
+            .iter()
+            .zip(other.registers.iter())
+            .filter(|(a, b)| a.value != b.value)
+            .map(|(a, b)| SnapshotDiff {
+                field: SnapshotField::Register(a.register),
+                old: a.value,
+                new: b.value,
+            });
+
+        let card_presence = (self.card.is_some() != other.card.is_some()).then(|| SnapshotDiff {
+            field: SnapshotField::Card,
+            old: self.card.map(|card| card.status),
+            new: other.card.map(|card| card.status),
+        });
+
+        let card_fields = self.card.zip(other.card).into_iter().flat_map(|(a, b)| {
+            let cid = (0..4).map(move |i| (SnapshotField::Cid(i), a.cid[i], b.cid[i]));
+            let csd = (0..4).map(move |i| (SnapshotField::Csd(i), a.csd[i], b.csd[i]));
+            let status = core::iter::once((SnapshotField::CardStatus, a.status, b.status));
+            cid.chain(csd)
+                .chain(status)
+                .filter(|(_, old, new)| old != new)
+                .map(|(field, old, new)| SnapshotDiff {
+                    field,
+                    old: Some(old),
+                    new: Some(new),
+                })
+        });
+
+        registers.chain(card_presence).chain(card_fields)
+    }
+}
+
+impl core::fmt::Display for RegisterSnapshot {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for reading in &self.registers {
+            writeln!(f, "{reading}")?;
+        }
+
+        match &self.card {
+            Some(card) => {
+                writeln!(f, "CID: {:08X?}", card.cid)?;
+                writeln!(f, "CSD: {:08X?}", card.csd)?;
+                writeln!(f, "Card status: {:#010X} ({:?})", card.status, card.state())?;
+            }
+            None => writeln!(f, "Card: not initialized")?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> RegisterSnapshot {
+        RegisterSnapshot {
+            registers: Register::ALL.map(|register| RegisterReading {
+                register,
+                value: (register != Register::DataFifo).then_some(0),
+            }),
+            card: Some(CardState {
+                cid: [0x0F4E59BF, 0x3932009D, 0x30303847, 0x00110100],
+                csd: [0; 4],
+                status: 0x900,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_card_state() {
+        assert_eq!(snapshot().card.unwrap().state(), Some(MmcState::Transfer));
+    }
+
+    #[test]
+    fn test_diff() {
+        let a = snapshot();
+        let mut b = snapshot();
+        assert_eq!(a.diff(&b).count(), 0);
+
+        b.registers[9].value = Some(0x800020);
+        b.card.as_mut().unwrap().cid[3] = 0x00110200;
+
+        let diff: Vec<_> = a.diff(&b).collect();
+        assert_eq!(diff.len(), 2);
+        assert_eq!(diff[0].field, SnapshotField::Register(Register::Reg_0A));
+        assert_eq!(format!("{}", diff[1]), "CID[3] 0x00110100 0x00110200");
+
+        b.card = None;
+        let diff: Vec<_> = a.diff(&b).collect();
+        assert_eq!(diff[1].field, SnapshotField::Card);
+    }
+
+    #[test]
+    fn test_display_decodes_registers() {
+        let mut snap = snapshot();
+        snap.registers[11].value = Some(0x21);
+        let text = format!("{snap}");
+        assert!(text.contains("DataFifo: not read\n"));
+        assert!(text.contains("InterruptStatus { bits: 0x00000021, command_complete: true, buffer_read_ready: true }\n"));
+        assert!(text.contains("Card status: 0x00000900 (Some(Transfer))\n"));
+    }
+}
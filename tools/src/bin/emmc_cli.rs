@@ -1,26 +1,59 @@
 use clap::{Parser, Subcommand};
 use indicatif::{ProgressIterator, ProgressStyle};
+use libaspect2::Facet2Board;
+use libaspect2::board::list_adapters;
 use libaspect2::spi::backend::SpiBackend;
-use libaspect2::spi::backend::ftdi::FtdiBackend;
-use libaspect2::spi::emmc_reader::EmmcReader;
+use libaspect2::spi::emmc_reader::{DEFAULT_BLOCK_COUNT, EmmcReader};
+use libaspect2::spi::snapshot::RegisterSnapshot;
 use libaspect2::DelayTrait;
 use std::fs::File;
 use std::io::Write;
+use std::path::PathBuf;
 use std::time::Duration;
 
-const MAX_NAND_PAGES: u32 = 0x9E0000;
-
 #[derive(Subcommand, Clone, PartialEq, Debug)]
 enum Command {
+    /// List connected adapters
+    List,
     Reset,
     Read,
     Write,
     DumpFuses,
+    /// Probe all controller registers and print a diffable report
+    Scan {
+        /// Write the report to a file instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Capture controller and card registers
+    Snapshot {
+        /// Save the snapshot as JSON
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Print differences to a previously saved snapshot
+        #[arg(long)]
+        compare: Option<PathBuf>,
+        /// Also read CID, CSD and status (deselects and reselects the card)
+        #[arg(long)]
+        card: bool,
+    },
 }
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
+    /// Serial number of the adapter to use (without channel letter)
+    #[arg(long, global = true)]
+    serial: Option<String>,
+    /// Index of the adapter to use, as printed by `list`
+    #[arg(long, global = true, conflicts_with = "serial")]
+    index: Option<usize>,
+    /// SPI clock frequency in Hz
+    #[arg(long, global = true)]
+    clock_hz: Option<u32>,
+    /// Turnaround clock cycles between read header and response
+    #[arg(long, global = true)]
+    turnaround: Option<u16>,
     #[command(subcommand)]
     op: Command,
 }
@@ -38,13 +71,29 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let args = Args::parse();
 
-    // Open FTDI device
-    let backend = FtdiBackend::open("Facet2 FabA+ A")?;
+    if args.op == Command::List {
+        for (index, adapter) in list_adapters()?.iter().enumerate() {
+            println!("[{index}] {adapter}");
+        }
+        return Ok(());
+    }
+
+    // Open eMMC SPI channel of the selected board
+    let mut board = Facet2Board::select(args.serial.as_deref(), args.index)?;
+    let mut backend = board.emmc()?;
+
+    if let Some(clock_hz) = args.clock_hz {
+        backend.set_clock_hz(clock_hz)?;
+    }
+    if let Some(cycles) = args.turnaround {
+        backend.set_turnaround_cycles(cycles)?;
+    }
 
     // Create reader with FTDI backend
     let mut reader = EmmcReader::new(backend, Delay);
 
     match args.op {
+        Command::List => unreachable!(),
         Command::Reset => {
             println!("Resetting device...");
             reader.backend.reset()?;
@@ -55,6 +104,46 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             reader.dump_fuses()?;
             return Ok(());
         }
+        Command::Scan { ref output } => {
+            println!("Initializing device...");
+            reader.init()?;
+
+            println!("Scanning registers...");
+            let scan = reader.scan_registers()?;
+            match output {
+                Some(path) => std::fs::write(path, scan.to_string())?,
+                None => print!("{scan}"),
+            }
+            return Ok(());
+        }
+        Command::Snapshot {
+            ref output,
+            ref compare,
+            card,
+        } => {
+            println!("Initializing device...");
+            reader.init()?;
+
+            let snapshot = if card {
+                reader.dump_mmc_registers_with_card()?
+            } else {
+                reader.dump_mmc_registers()?
+            };
+            if let Some(path) = output {
+                std::fs::write(path, serde_json::to_string_pretty(&snapshot)?)?;
+            }
+            match compare {
+                Some(path) => {
+                    let previous: RegisterSnapshot =
+                        serde_json::from_str(&std::fs::read_to_string(path)?)?;
+                    for diff in previous.diff(&snapshot) {
+                        println!("{diff}");
+                    }
+                }
+                None => print!("{snapshot}"),
+            }
+            return Ok(());
+        }
         Command::Write | Command::Read => {
             if args.op == Command::Write {
                 todo!("Learn to read first");
@@ -74,7 +163,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             // Read eMMC pages
             println!("Reading eMMC...");
             // Chunking?
-            for page_num in (0..MAX_NAND_PAGES)
+            for page_num in (0..DEFAULT_BLOCK_COUNT)
             .progress()
             .with_style(
                 ProgressStyle::default_spinner()
@@ -1,14 +1,26 @@
 use std::time::Duration;
 
 use anyhow::{Result, anyhow};
-use libaspect2::Ft4232h;
-use libaspect2::i2c::i2c_bitbang::I2cFtBitbang;
+use clap::Parser;
+use libaspect2::Facet2Board;
 use libaspect2::embedded_hal::i2c::{I2c, Operation};
 use rand::prelude::*;
 
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// Serial number of the adapter to use (without channel letter)
+    #[arg(long)]
+    serial: Option<String>,
+    /// Index of the adapter to use
+    #[arg(long, conflicts_with = "serial")]
+    index: Option<usize>,
+}
+
 fn main() -> Result<()> {
-    let device = Ft4232h::with_description("Facet2 FabA+ C")?;
-    let mut i2c_if = I2cFtBitbang::new(device);
+    let args = Args::parse();
+
+    let mut i2c_if = Facet2Board::select(args.serial.as_deref(), args.index)?.i2c()?;
 
     let mut rng = rand::rng();
 
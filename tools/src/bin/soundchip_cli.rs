@@ -1,21 +1,57 @@
 use std::io::{Read, Write};
-use libaspect2::Ft4232h;
-use libaspect2::i2c::i2c_bitbang::I2cFtBitbang;
-use libaspect2::i2c::isd9160::{self, Isd9160, Isd9160Sounds};
+use clap::Parser;
+use std::time::Duration;
+use libaspect2::{DelayTrait, Facet2Board};
+use libaspect2::i2c::isd9160::{self, Isd9160, VerifyMode};
 use indicatif::{ProgressIterator, ProgressStyle};
 
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// Serial number of the adapter to use (without channel letter)
+    #[arg(long)]
+    serial: Option<String>,
+    /// Index of the adapter to use
+    #[arg(long, conflicts_with = "serial")]
+    index: Option<usize>,
+    /// Read every flash chunk until two reads agree
+    #[arg(long)]
+    verify: bool,
+    /// Play a sound, by index
+    #[arg(long)]
+    play: Option<u8>,
+    /// Print the I2C peripheral configuration of the chip
+    #[arg(long)]
+    config: bool,
+}
+
+struct Delay;
+
+impl DelayTrait for Delay {
+    fn delay_ns(&mut self, ns: u32) {
+        std::thread::sleep(Duration::from_nanos(ns as u64));
+    }
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     simple_logger::init_with_level(log::Level::Warn)?;
 
-    let device = Ft4232h::with_description("Facet2 FabA+ C")?;
-    let i2c_if = I2cFtBitbang::new(device);
-    let mut isd = Isd9160::new(i2c_if);
+    let args = Args::parse();
+
+    let i2c_if = Facet2Board::select(args.serial.as_deref(), args.index)?.i2c()?;
+    let verify = if args.verify { VerifyMode::DoubleRead } else { VerifyMode::None };
+    let mut isd = Isd9160::new(i2c_if, Delay).with_verify(verify);
 
-    isd.init();
-    isd.stop();
+    isd.init()?;
+    isd.stop()?;
+
+    if args.config {
+        print!("{}", isd.read_config()?);
+        return Ok(());
+    }
 
-    if false {
-        isd.play_sound(Isd9160Sounds::NO_DISC);
+    if let Some(index) = args.play {
+        isd.play_sound(index)?;
     } else {
         let mut buf = vec![0u8; isd9160::READ_CHUNK_SIZE];
         println!("Reading flash...");